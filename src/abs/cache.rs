@@ -0,0 +1,128 @@
+use std::fs;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+/// Objects and static archives shared between build directories, profiles
+/// and checkouts. Entries are addressed by the hash of everything they are
+/// built from, so a hit is reused instead of running the compiler.
+///
+/// The cache is opt-in through `cache = true` in the `[toolchain]` table.
+/// Entries are never removed by abs, the directory can be deleted at any time.
+#[derive(Debug)]
+pub struct ObjectCache {
+    directory: String,
+}
+
+/// Inputs of a cached artifact
+pub struct CacheKey {
+    hasher: Sha256,
+    /// Prefix which is cut from paths, so checkouts in different places share entries.
+    /// Objects are compiled with the prefix mapped to '.' for that.
+    base_directory: String,
+}
+
+impl CacheKey {
+    pub fn new(kind: &str, base_directory: &str) -> CacheKey {
+        let mut key = CacheKey {
+            hasher: Sha256::new(),
+            base_directory: base_directory.to_string(),
+        };
+        key.add_text(kind);
+        key
+    }
+
+    fn normalize(&self, text: &str) -> String {
+        if self.base_directory.is_empty() {
+            text.to_string()
+        } else {
+            text.replace(&self.base_directory, ".")
+        }
+    }
+
+    pub fn add_text(&mut self, text: &str) {
+        let text = self.normalize(text);
+        self.hasher.update((text.len() as u64).to_le_bytes());
+        self.hasher.update(text.as_bytes());
+    }
+
+    /// Returns false when the file can't be read, such artifact isn't cached
+    pub fn add_content(&mut self, path: &str) -> bool {
+        match fs::read(path) {
+            Ok(content) => {
+                self.hasher.update((content.len() as u64).to_le_bytes());
+                self.hasher.update(&content);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    pub fn add_file(&mut self, path: &str) -> bool {
+        self.add_text(path);
+        self.add_content(path)
+    }
+
+    pub fn finish(self) -> String {
+        self.hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+}
+
+impl ObjectCache {
+    /// `ABS_CACHE_DIR` overrides location of the cache
+    pub fn new() -> ObjectCache {
+        let directory = match std::env::var("ABS_CACHE_DIR") {
+            Ok(directory) if !directory.is_empty() => directory,
+            _ => match std::env::var("XDG_CACHE_HOME") {
+                Ok(cache) if !cache.is_empty() => format!("{cache}/abs"),
+                _ => format!("{}/.cache/abs", std::env::var("HOME").unwrap_or_else(|_| ".".to_string())),
+            },
+        };
+        ObjectCache { directory }
+    }
+
+    fn get_entry_path(&self, key: &str, extension: &str) -> String {
+        format!("{}/{}/{}.{}", self.directory, &key[..2], key, extension)
+    }
+
+    /// Places the cached artifact at the destination, returns false on a miss
+    pub fn restore(&self, key: &str, extension: &str, destination: &str) -> bool {
+        let entry = self.get_entry_path(key, extension);
+        if !Path::new(&entry).is_file() {
+            return false;
+        }
+        let _ = fs::remove_file(destination);
+        fs::hard_link(&entry, destination).is_ok() || fs::copy(&entry, destination).is_ok()
+    }
+
+    /// Artifact is written aside and renamed, so a reader never sees a partial entry
+    pub fn store(&self, key: &str, extension: &str, source: &str) {
+        let entry = self.get_entry_path(key, extension);
+        if Path::new(&entry).exists() || fs::create_dir_all(format!("{}/{}", self.directory, &key[..2])).is_err() {
+            return;
+        }
+        let temporary = format!("{}.{}.tmp", entry, std::process::id());
+        let _ = fs::remove_file(&temporary);
+        let is_written = fs::hard_link(source, &temporary).is_ok() || fs::copy(source, &temporary).is_ok();
+        if is_written && fs::rename(&temporary, &entry).is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn keys_ignore_base_directory() {
+        let mut first = CacheKey::new("object", "/home/user/first");
+        first.add_text("-I/home/user/first/include");
+        let mut second = CacheKey::new("object", "/home/user/second");
+        second.add_text("-I/home/user/second/include");
+        assert_eq!(first.finish(), second.finish());
+    }
+}
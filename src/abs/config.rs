@@ -0,0 +1,42 @@
+//! Helpers for reading typed values from the configuration file
+
+pub fn get_string(config: &toml::Value, key: &str) -> Result<Option<String>, String> {
+    match config.get(key) {
+        Some(value) => value
+            .as_str()
+            .map(|value| Some(value.to_string()))
+            .ok_or_else(|| format!("'{key}' is string type")),
+        None => Ok(None),
+    }
+}
+
+/// Reads a value which can be written either as a string or as an array of strings
+pub fn get_strings(config: &toml::Value, key: &str) -> Result<Option<Vec<String>>, String> {
+    match config.get(key) {
+        Some(toml::Value::String(value)) => Ok(Some(vec![value.clone()])),
+        Some(toml::Value::Array(values)) => values
+            .iter()
+            .map(|value| value.as_str().map(str::to_string))
+            .collect::<Option<Vec<String>>>()
+            .map(Some)
+            .ok_or_else(|| format!("'{key}' array can contain only string type")),
+        Some(_) => Err(format!("'{key}' can be only an array or a string")),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn reading_strings() {
+        let config: toml::Value = toml::from_str("one = \"a\"\nmany = [\"a\", \"b\"]\nwrong = [1]").unwrap();
+        assert_eq!(get_strings(&config, "one"), Ok(Some(vec!["a".to_string()])));
+        assert_eq!(
+            get_strings(&config, "many"),
+            Ok(Some(vec!["a".to_string(), "b".to_string()]))
+        );
+        assert!(get_strings(&config, "wrong").is_err());
+        assert_eq!(get_strings(&config, "missing"), Ok(None));
+    }
+}
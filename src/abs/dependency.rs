@@ -21,6 +21,6 @@ impl Dependency {
                 return Some(File::new(&dependency_path).unwrap());
             }
         }
-        return None;
+        None
     }
 }
\ No newline at end of file
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use super::file::File;
+
+/// Includes of a file as they were found when the file had the modification time
+#[derive(Debug, Clone, PartialEq)]
+struct CachedFile {
+    modified: i64,
+    includes: Vec<String>,
+}
+
+/// Includes of section files and headers they reach kept in the build directory between runs, so
+/// files are read only when they changed since the previous scan. The whole
+/// cache is dropped when the include directories change.
+#[derive(Debug)]
+pub struct DependencyCache {
+    path: String,
+    search_list: Vec<String>,
+    files: HashMap<String, CachedFile>,
+    is_changed: bool,
+}
+
+impl DependencyCache {
+    pub fn load(path: &str, search_list: &[String]) -> DependencyCache {
+        let mut cache = DependencyCache {
+            path: path.to_string(),
+            search_list: search_list.to_vec(),
+            files: HashMap::new(),
+            is_changed: false,
+        };
+        if let Some(files) = fs::read_to_string(path)
+            .ok()
+            .and_then(|content| DependencyCache::parse(&content, search_list))
+        {
+            cache.files = files;
+        }
+        cache
+    }
+
+    /// Returns None when the content is broken or was scanned with other include directories
+    fn parse(content: &str, search_list: &[String]) -> Option<HashMap<String, CachedFile>> {
+        let config: toml::Value = toml::from_str(content).ok()?;
+        let strings = |value: &toml::Value| -> Option<Vec<String>> {
+            value
+                .as_array()?
+                .iter()
+                .map(|item| item.as_str().map(str::to_string))
+                .collect()
+        };
+        if strings(config.get("search_list")?)? != search_list {
+            return None;
+        }
+        config
+            .get("files")?
+            .as_array()?
+            .iter()
+            .map(|entry| {
+                let path = entry.get("path")?.as_str()?.to_string();
+                let file = CachedFile {
+                    modified: entry.get("modified")?.as_integer()?,
+                    includes: strings(entry.get("includes")?)?,
+                };
+                Some((path, file))
+            })
+            .collect()
+    }
+
+    fn to_toml(&self) -> String {
+        let mut paths: Vec<&String> = self.files.keys().collect();
+        paths.sort();
+        let files: Vec<toml::Value> = paths
+            .into_iter()
+            .map(|path| {
+                let file = &self.files[path];
+                let mut table = toml::value::Table::new();
+                table.insert("path".to_string(), toml::Value::String(path.clone()));
+                table.insert("modified".to_string(), toml::Value::Integer(file.modified));
+                table.insert(
+                    "includes".to_string(),
+                    toml::Value::Array(file.includes.iter().cloned().map(toml::Value::String).collect()),
+                );
+                toml::Value::Table(table)
+            })
+            .collect();
+        let mut config = toml::value::Table::new();
+        config.insert(
+            "search_list".to_string(),
+            toml::Value::Array(self.search_list.iter().cloned().map(toml::Value::String).collect()),
+        );
+        config.insert("files".to_string(), toml::Value::Array(files));
+        toml::to_string(&toml::Value::Table(config)).unwrap_or_default()
+    }
+
+    /// Headers included by the file, the file is read only when the cached entry is outdated
+    pub fn get_dependencies(&mut self, file: &File) -> Vec<File> {
+        self.get(file, File::collect_dependencies)
+    }
+
+    /// Same for headers reached through includes. Their includes which can't be found
+    /// are skipped, headers of other tanks and packages are scanned with this search list too.
+    pub fn get_header_dependencies(&mut self, file: &File) -> Vec<File> {
+        self.get(file, File::collect_found_dependencies)
+    }
+
+    fn get(&mut self, file: &File, collect: fn(&File, &[String]) -> Vec<File>) -> Vec<File> {
+        if let Some(cached) = self.files.get(&file.path) {
+            if cached.modified == file.get_modification_nanos() {
+                let includes: Option<Vec<File>> = cached
+                    .includes
+                    .iter()
+                    .map(|include| File::new(include).ok())
+                    .collect();
+                if let Some(includes) = includes {
+                    return includes;
+                }
+            }
+        }
+        let includes = collect(file, &self.search_list);
+        self.files.insert(
+            file.path.clone(),
+            CachedFile {
+                modified: file.get_modification_nanos(),
+                includes: includes.iter().map(|include| include.path.clone()).collect(),
+            },
+        );
+        self.is_changed = true;
+        includes
+    }
+
+    /// Entries of files which are no longer scanned are dropped when the cache is saved
+    pub fn save(&mut self, files: &[File]) {
+        let before = self.files.len();
+        self.files.retain(|path, _| files.iter().any(|file| &file.path == path));
+        if !self.is_changed && before == self.files.len() {
+            return;
+        }
+        if let Some(directory) = Path::new(&self.path).parent() {
+            let _ = fs::create_dir_all(directory);
+        }
+        let _ = fs::write(&self.path, self.to_toml());
+        self.is_changed = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn cache_is_dropped_for_other_search_list() {
+        let mut cache = DependencyCache::load("", &["include".to_string()]);
+        cache.files.insert(
+            "main.cpp".to_string(),
+            CachedFile {
+                modified: 42,
+                includes: vec!["include/a.hpp".to_string()],
+            },
+        );
+        let content = cache.to_toml();
+        let files = DependencyCache::parse(&content, &["include".to_string()]).unwrap();
+        assert_eq!(files["main.cpp"], cache.files["main.cpp"]);
+        assert!(DependencyCache::parse(&content, &["other".to_string()]).is_none());
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashSet;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+/// Single message reported by GCC or Clang in the
+/// `file:line:column: severity: message` form.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub column: Option<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn parse(line: &str) -> Option<Diagnostic> {
+        let markers = [
+            (": fatal error: ", Severity::Error),
+            (": error: ", Severity::Error),
+            (": warning: ", Severity::Warning),
+            (": note: ", Severity::Note),
+        ];
+        let (position, marker, severity) = markers
+            .iter()
+            .filter_map(|(marker, severity)| {
+                line.find(marker).map(|position| (position, *marker, *severity))
+            })
+            .min_by_key(|(position, _, _)| *position)?;
+
+        let location = &line[..position];
+        let message = line[position + marker.len()..].to_string();
+
+        let mut parts = location.rsplitn(3, ':');
+        let last = parts.next()?.parse::<usize>().ok()?;
+        let middle = parts.next()?;
+        let (file, line_number, column) = match middle.parse::<usize>() {
+            Ok(line_number) => (parts.next()?, line_number, Some(last)),
+            Err(_) => match parts.next() {
+                Some(rest) => (&location[..rest.len() + middle.len() + 1], last, None),
+                None => (middle, last, None),
+            },
+        };
+        if file.is_empty() {
+            return None;
+        }
+
+        Some(Diagnostic {
+            file: file.to_string(),
+            line: line_number,
+            column,
+            severity,
+            message,
+        })
+    }
+
+    pub fn collect(output: &str) -> Vec<Diagnostic> {
+        output.lines().filter_map(Diagnostic::parse).collect()
+    }
+}
+
+/// Errors and warnings reported while processing a section. A diagnostic of a
+/// header is reported by every unit including it, so repeated ones are counted once.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DiagnosticsSummary {
+    pub errors: usize,
+    pub warnings: usize,
+    seen: HashSet<Diagnostic>,
+    files: HashSet<String>,
+}
+
+impl DiagnosticsSummary {
+    pub fn add(&mut self, diagnostics: &[Diagnostic]) {
+        for diagnostic in diagnostics {
+            if diagnostic.severity == Severity::Note || !self.seen.insert(diagnostic.clone()) {
+                continue;
+            }
+            match diagnostic.severity {
+                Severity::Error => self.errors += 1,
+                Severity::Warning => self.warnings += 1,
+                Severity::Note => {}
+            }
+            self.files.insert(diagnostic.file.clone());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors == 0 && self.warnings == 0
+    }
+}
+
+impl fmt::Display for DiagnosticsSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = |count: usize| if count == 1 { "" } else { "s" };
+        write!(
+            f,
+            "{} error{}, {} warning{} in {} file{}",
+            self.errors,
+            plural(self.errors),
+            self.warnings,
+            plural(self.warnings),
+            self.files.len(),
+            plural(self.files.len())
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_diagnostics() {
+        assert_eq!(
+            Diagnostic::parse("source/main.cpp:5:3: error: 'foo' was not declared in this scope"),
+            Some(Diagnostic {
+                file: "source/main.cpp".to_string(),
+                line: 5,
+                column: Some(3),
+                severity: Severity::Error,
+                message: "'foo' was not declared in this scope".to_string(),
+            })
+        );
+        assert_eq!(
+            Diagnostic::parse("main.cpp:12: warning: unused variable 'x'"),
+            Some(Diagnostic {
+                file: "main.cpp".to_string(),
+                line: 12,
+                column: None,
+                severity: Severity::Warning,
+                message: "unused variable 'x'".to_string(),
+            })
+        );
+        assert_eq!(
+            Diagnostic::parse("a.cpp:1:10: fatal error: missing.hpp: No such file or directory")
+                .map(|diagnostic| diagnostic.severity),
+            Some(Severity::Error)
+        );
+        assert_eq!(Diagnostic::parse("In file included from main.cpp:1:"), None);
+        assert_eq!(Diagnostic::parse("    5 |     foo();"), None);
+    }
+
+    #[test]
+    fn summarizing_diagnostics() {
+        let output = "main.cpp:1:1: error: first\n\
+                      main.cpp:2:1: warning: second\n\
+                      main.cpp:2:1: note: third\n\
+                      main.cpp:3:1: error: fourth\n\
+                      header.hpp:4:1: warning: fifth\n";
+        let mut summary = DiagnosticsSummary::default();
+        summary.add(&Diagnostic::collect(output));
+        // Other unit including the same header
+        summary.add(&Diagnostic::collect("header.hpp:4:1: warning: fifth\n"));
+        assert_eq!((summary.errors, summary.warnings), (2, 2));
+        assert_eq!(summary.to_string(), "2 errors, 2 warnings in 2 files");
+    }
+}
@@ -41,8 +41,8 @@ impl File {
         })
     }
 
-    fn get_frozen_time_in(&self, subdirectory: &str) -> Option<NaiveDateTime> {
-        let f = fs::File::open(self.get_freeze_path_in(subdirectory));
+    fn get_frozen_time_in(&self, directory: &str) -> Option<NaiveDateTime> {
+        let f = fs::File::open(self.get_freeze_path_in(directory));
         if f.is_err() {
             return None;
         }
@@ -52,14 +52,10 @@ impl File {
         if std::io::BufRead::read_line(&mut reader, &mut content).is_err() {
             return None;
         }
-        if let Ok(time) = NaiveDateTime::parse_from_str(&content, "%Y-%m-%d/%T") {
-            return Some(time);
-        } else {
-            return None;
-        }
+        NaiveDateTime::parse_from_str(&content, "%Y-%m-%d/%T").ok()
     }
 
-    pub fn get_object_path_in(&self, subdirectory: &str) -> Result<String, FileError> {
+    pub fn get_object_path_in(&self, directory: &str) -> Result<String, FileError> {
         let without_extension = self.path
             .strip_suffix(".cpp")
             .or_else(|| self.path.strip_suffix(".c"))
@@ -67,18 +63,43 @@ impl File {
 
         let file = File::encode_path(without_extension);
 
-        Ok(format!(".abs/{subdirectory}/binary/{file}.o"))
+        Ok(format!("{directory}/binary/{file}.o"))
     }
 
-    pub fn get_freeze_path_in(&self, subdirectory: &str) -> String {
+    pub fn get_freeze_path_in(&self, directory: &str) -> String {
         let encoded_file = File::encode_path(&self.path);
-        format!(".abs/{subdirectory}/frozen/{encoded_file}.frozen")
+        format!("{directory}/frozen/{encoded_file}.frozen")
     }
 
     pub fn collect_dependencies(&self, search_list: &[String]) -> Vec<File> {
+        self.find_dependencies(search_list)
+            .into_iter()
+            .map(|dependency| {
+                dependency.unwrap_or_else(|name| {
+                    println!(
+                        "{:>RESULT_BORDER_WIDTH$} {}",
+                        "Failed to find ".bright_red(),
+                        name
+                    );
+                    std::process::exit(1);
+                })
+            })
+            .collect()
+    }
+
+    /// Includes which are found in the search list, the rest is left for the compiler to report
+    pub fn collect_found_dependencies(&self, search_list: &[String]) -> Vec<File> {
+        self.find_dependencies(search_list)
+            .into_iter()
+            .filter_map(Result::ok)
+            .collect()
+    }
+
+    /// Each include of the file, either found or with the name which isn't found
+    fn find_dependencies(&self, search_list: &[String]) -> Vec<Result<File, String>> {
         let temp = Path::new(&self.path).canonicalize().unwrap();
         let path_to_file = temp.parent().unwrap().to_str().unwrap();
-        let search_list: Vec<String> = search_list.to_vec().into_iter().chain(vec![path_to_file.to_string()]).collect();
+        let search_list: Vec<String> = search_list.iter().cloned().chain(vec![path_to_file.to_string()]).collect();
 
         let file = fs::File::open(&self.path).unwrap();
         let reader = std::io::BufReader::new(file);
@@ -90,28 +111,24 @@ impl File {
                 let stripped = line.strip_prefix("#include ").unwrap().trim();
                 let name = stripped[1..stripped.len() - 1].to_string();
                 let dependency = Dependency::new(name);
-
-                if let Some(val) = dependency.get_file_from_path(&search_list) {
-                    return val;
-                } else {
-                    println!(
-                        "{:>RESULT_BORDER_WIDTH$} {}",
-                        "Failed to find ".bright_red(),
-                        dependency.name
-                    );
-                    std::process::exit(1);
-                }
+                dependency
+                    .get_file_from_path(&search_list)
+                    .ok_or(dependency.name)
             })
             .collect()
     }
 
-    pub fn is_modified_in(&self, subdirectory: &str) -> bool {
-        match self.get_frozen_time_in(subdirectory) {
+    pub fn is_modified_in(&self, directory: &str) -> bool {
+        match self.get_frozen_time_in(directory) {
             Some(frozen_time) => self.last_modification.timestamp() > frozen_time.timestamp(),
             None => true,
         }
     }
 
+    pub fn get_modification_nanos(&self) -> i64 {
+        self.last_modification.timestamp_nanos()
+    }
+
     pub fn modification_time_to_string(&self) -> String {
         self.last_modification.format("%Y-%m-%d/%T").to_string()
     }
@@ -131,7 +148,8 @@ impl File {
                 vec_of_paths.push(File::new(full_path).unwrap());
             }
         }
-        return vec_of_paths;
+        vec_of_paths.sort_by(|left, right| left.path.cmp(&right.path));
+        vec_of_paths
     }
 
     pub fn encode_path(path: &str) -> String {
@@ -147,12 +165,12 @@ impl File {
             } else {
                 if !temp.is_empty() {
                     result += &temp.len().to_string();
-                    result += &temp;
+                    result += temp;
                 }
                 break;
             }
         }
-        return result;
+        result
     }
 }
 
@@ -0,0 +1,143 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use super::file::File;
+use super::lockfile::{LockedGit, Lockfile};
+
+/// Directory shared between all tanks of the user. `ABS_HOME` overrides it.
+pub fn get_cache_directory() -> String {
+    match std::env::var("ABS_HOME") {
+        Ok(home) if !home.is_empty() => home,
+        _ => format!("{}/.abs", std::env::var("HOME").unwrap_or_else(|_| ".".to_string())),
+    }
+}
+
+fn git(arguments: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(arguments)
+        .output()
+        .map_err(|err| format!("Failed to run git: {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "'git {}' failed: {}",
+            arguments.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn resolve(database: &str, rev: &str) -> Result<String, String> {
+    git(&["-C", database, "rev-parse", "--verify", "--quiet", &format!("{rev}^{{commit}}")])
+}
+
+/// Clones the repository into the cache or fetches new commits
+fn update(url: &str, database: &str) -> Result<(), String> {
+    if Path::new(database).exists() {
+        git(&[
+            "-C",
+            database,
+            "fetch",
+            "--quiet",
+            "--force",
+            "--tags",
+            url,
+            "+refs/heads/*:refs/heads/*",
+        ])
+        .map(|_| ())
+    } else {
+        git(&["clone", "--quiet", "--bare", url, database]).map(|_| ())
+    }
+}
+
+/// Returns directory with the dependency checked out at the commit
+/// pinned in the lockfile or the one `rev` currently points to
+pub fn fetch(name: &str, url: &str, rev: &str, lockfile: &mut Lockfile) -> Result<String, String> {
+    let cache = format!("{}/git", get_cache_directory());
+    let database = format!("{cache}/db/{}", File::encode_path(url));
+    fs::create_dir_all(format!("{cache}/db")).map_err(|err| err.to_string())?;
+
+    let commit = match lockfile.find(url, rev).map(|locked| locked.commit.clone()) {
+        Some(commit) => {
+            if !Path::new(&database).exists() || resolve(&database, &commit).is_err() {
+                update(url, &database)?;
+            }
+            resolve(&database, &commit)
+                .map_err(|_| format!("Locked commit {commit} doesn't exist in '{url}'"))?
+        }
+        None => {
+            update(url, &database)?;
+            resolve(&database, rev).map_err(|_| format!("Can't find revision '{rev}' in '{url}'"))?
+        }
+    };
+
+    let checkout = format!("{cache}/checkouts/{}/{commit}", File::encode_path(url));
+    if !Path::new(&checkout).exists() {
+        fs::create_dir_all(format!("{cache}/checkouts/{}", File::encode_path(url))).map_err(|err| err.to_string())?;
+        // Checkout is prepared aside so an interrupted one is never taken as complete
+        let temporary = format!("{checkout}.tmp");
+        let _ = fs::remove_dir_all(&temporary);
+        git(&["clone", "--quiet", "--no-checkout", &database, &temporary])?;
+        git(&["-C", &temporary, "checkout", "--quiet", "--detach", &commit])?;
+        fs::rename(&temporary, &checkout).map_err(|err| err.to_string())?;
+    }
+
+    lockfile.lock(LockedGit {
+        name: name.to_string(),
+        url: url.to_string(),
+        rev: rev.to_string(),
+        commit,
+    });
+    Ok(checkout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Commits a file into the work tree and pushes it to the bare repository, returns the commit
+    fn commit(work: &str, content: &str) -> String {
+        fs::write(format!("{work}/abs.toml"), content).unwrap();
+        git(&["-C", work, "add", "abs.toml"]).unwrap();
+        git(&["-C", work, "-c", "user.name=abs", "-c", "user.email=abs@localhost", "commit", "--quiet", "-m", content])
+            .unwrap();
+        git(&["-C", work, "push", "--quiet", "origin", "HEAD:refs/heads/main"]).unwrap();
+        git(&["-C", work, "rev-parse", "HEAD"]).unwrap()
+    }
+
+    #[test]
+    fn fetching_from_local_bare_repository() {
+        let directory = std::env::temp_dir().join(format!("abs-git-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let directory = directory.to_string_lossy().to_string();
+        std::env::set_var("ABS_HOME", format!("{directory}/home"));
+        let url = format!("{directory}/origin.git");
+        let work = format!("{directory}/work");
+        git(&["init", "--quiet", "--bare", "--initial-branch=main", &url]).unwrap();
+        git(&["init", "--quiet", &work]).unwrap();
+        git(&["-C", &work, "remote", "add", "origin", &url]).unwrap();
+        let first = commit(&work, "first");
+
+        let lockfile_path = format!("{directory}/abs.lock");
+        let mut lockfile = Lockfile::load(&lockfile_path).unwrap();
+        let checkout = fetch("origin", &url, "main", &mut lockfile).unwrap();
+        assert!(checkout.ends_with(&first));
+        assert_eq!(fs::read_to_string(format!("{checkout}/abs.toml")).unwrap(), "first");
+        lockfile.save().unwrap();
+
+        // The pinned commit is kept after the branch moves on until the lockfile is dropped
+        let second = commit(&work, "second");
+        let mut lockfile = Lockfile::load(&lockfile_path).unwrap();
+        assert!(fetch("origin", &url, "main", &mut lockfile).unwrap().ends_with(&first));
+        let mut lockfile = Lockfile::load(&format!("{directory}/other.lock")).unwrap();
+        assert!(fetch("origin", &url, "main", &mut lockfile).unwrap().ends_with(&second));
+
+        assert_eq!(
+            fetch("origin", &url, "missing", &mut lockfile),
+            Err(format!("Can't find revision 'missing' in '{url}'"))
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,142 @@
+use std::fs;
+use std::path::Path;
+
+/// Paths in patterns are separated by '/'. `*` and `?` match inside of a single
+/// component, `[abc]`, `[a-z]` and `[!abc]` match one character of the class
+/// and `**` matches any number of components, including none.
+pub fn is_pattern(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+pub fn matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = split(pattern);
+    let path: Vec<&str> = split(path);
+    match_components(&pattern, &path)
+}
+
+/// Directory pattern like `src/win32` excludes everything inside of it
+pub fn matches_path_or_parent(pattern: &str, path: &str) -> bool {
+    let components = split(path);
+    (1..=components.len()).any(|length| matches(pattern, &components[..length].join("/")))
+}
+
+/// Returns files under `root` which match the pattern. Paths are
+/// returned the way the pattern is written, i.e. relative to `root`.
+pub fn expand(root: &str, pattern: &str) -> Vec<String> {
+    let components = split(pattern);
+    let literal = components
+        .iter()
+        .take_while(|component| !is_pattern(component))
+        .count();
+    let mut prefix = components[..literal].join("/");
+    if pattern.starts_with('/') {
+        prefix.insert(0, '/');
+    }
+    list_files(root, &prefix)
+        .into_iter()
+        .filter(|path| matches(pattern, path))
+        .collect()
+}
+
+/// Recursively lists files of the directory relative to `root`
+pub fn list_files(root: &str, directory: &str) -> Vec<String> {
+    let full = if directory.is_empty() {
+        root.to_string()
+    } else if Path::new(directory).is_absolute() {
+        directory.to_string()
+    } else {
+        format!("{root}/{directory}")
+    };
+    let mut files = vec![];
+    let entries = match fs::read_dir(&full) {
+        Ok(entries) => entries,
+        Err(_) => return files,
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let relative = if directory.is_empty() {
+            name
+        } else {
+            format!("{}/{}", directory.trim_end_matches('/'), name)
+        };
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => files.append(&mut list_files(root, &relative)),
+            Ok(_) => files.push(relative),
+            Err(_) => {}
+        }
+    }
+    files
+}
+
+fn split(path: &str) -> Vec<&str> {
+    path.split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+        .collect()
+}
+
+fn match_components(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.first() {
+        None => path.is_empty(),
+        Some(&"**") => (0..=path.len()).any(|skipped| match_components(&pattern[1..], &path[skipped..])),
+        Some(component) => {
+            !path.is_empty()
+                && match_component(&component.chars().collect::<Vec<char>>(), &path[0].chars().collect::<Vec<char>>())
+                && match_components(&pattern[1..], &path[1..])
+        }
+    }
+}
+
+fn match_component(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skipped| match_component(&pattern[1..], &name[skipped..])),
+        Some('?') => !name.is_empty() && match_component(&pattern[1..], &name[1..]),
+        Some('[') => match (name.first(), pattern.iter().position(|&c| c == ']')) {
+            (Some(&c), Some(end)) if end > 1 => {
+                let (negated, class) = match pattern[1] {
+                    '!' | '^' => (true, &pattern[2..end]),
+                    _ => (false, &pattern[1..end]),
+                };
+                match_class(class, c) != negated && match_component(&pattern[end + 1..], &name[1..])
+            }
+            _ => name.first() == Some(&'[') && match_component(&pattern[1..], &name[1..]),
+        },
+        Some(&c) => name.first() == Some(&c) && match_component(&pattern[1..], &name[1..]),
+    }
+}
+
+fn match_class(class: &[char], c: char) -> bool {
+    let mut index = 0;
+    while index < class.len() {
+        if index + 2 < class.len() && class[index + 1] == '-' {
+            if class[index] <= c && c <= class[index + 2] {
+                return true;
+            }
+            index += 3;
+        } else {
+            if class[index] == c {
+                return true;
+            }
+            index += 1;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn matching_patterns() {
+        assert!(matches("src/**/*.cpp", "src/main.cpp"));
+        assert!(matches("src/**/*.cpp", "src/a/b/main.cpp"));
+        assert!(!matches("src/**/*.cpp", "src/main.hpp"));
+        assert!(!matches("src/*.cpp", "src/a/main.cpp"));
+        assert!(matches("./src/?ain.c", "src/main.c"));
+        assert!(matches("src/[a-c]*.cpp", "src/b.cpp"));
+        assert!(!matches("src/[!a-c]*.cpp", "src/b.cpp"));
+        assert!(matches("src/win32/**", "src/win32/io/file.cpp"));
+        assert!(matches_path_or_parent("src/experimental", "src/experimental/new.cpp"));
+        assert!(!matches_path_or_parent("src/experimental", "src/experiments.cpp"));
+    }
+}
@@ -0,0 +1,84 @@
+use super::json;
+
+/// Directed graph printed by `abs graph`, either of section pipes or of includes
+#[derive(Debug, Default)]
+pub struct Graph {
+    name: String,
+    nodes: Vec<String>,
+    edges: Vec<(String, String)>,
+}
+
+impl Graph {
+    pub fn new(name: &str) -> Graph {
+        Graph {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn add_node(&mut self, node: &str) {
+        if !self.nodes.iter().any(|existing| existing == node) {
+            self.nodes.push(node.to_string());
+        }
+    }
+
+    /// Adds both nodes as well, repeated edges are skipped
+    pub fn add_edge(&mut self, from: &str, to: &str) {
+        self.add_node(from);
+        self.add_node(to);
+        let edge = (from.to_string(), to.to_string());
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = format!("digraph \"{}\" {{\n", json::escape(&self.name));
+        for node in &self.nodes {
+            dot.push_str(&format!("    \"{}\";\n", json::escape(node)));
+        }
+        for (from, to) in &self.edges {
+            dot.push_str(&format!("    \"{}\" -> \"{}\";\n", json::escape(from), json::escape(to)));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_json(&self) -> String {
+        let nodes: Vec<String> = self
+            .nodes
+            .iter()
+            .map(|node| format!("\"{}\"", json::escape(node)))
+            .collect();
+        let edges: Vec<String> = self
+            .edges
+            .iter()
+            .map(|(from, to)| format!("{{\"from\": \"{}\", \"to\": \"{}\"}}", json::escape(from), json::escape(to)))
+            .collect();
+        format!("{{\"nodes\": [{}], \"edges\": [{}]}}", nodes.join(", "), edges.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn printing_graph() {
+        let mut graph = Graph::new("sections");
+        graph.add_edge("app", "core");
+        graph.add_edge("app", "core");
+        graph.add_node("tools");
+        assert_eq!(
+            graph.to_dot(),
+            "digraph \"sections\" {\n    \"app\";\n    \"core\";\n    \"tools\";\n    \"app\" -> \"core\";\n}\n"
+        );
+        assert_eq!(
+            graph.to_json(),
+            "{\"nodes\": [\"app\", \"core\", \"tools\"], \"edges\": [{\"from\": \"app\", \"to\": \"core\"}]}"
+        );
+    }
+}
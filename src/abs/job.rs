@@ -0,0 +1,111 @@
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Spawned process which output is captured instead of being
+/// interleaved with other processes running in parallel
+#[derive(Debug)]
+pub struct Job {
+    child: Child,
+    stderr: Option<JoinHandle<String>>,
+    started: Instant,
+}
+
+#[derive(Debug)]
+pub struct JobResult {
+    pub success: bool,
+    pub output: String,
+    pub duration: Duration,
+}
+
+impl JobResult {
+    pub fn from_spawn_error(error: std::io::Error) -> JobResult {
+        JobResult {
+            success: false,
+            output: error.to_string(),
+            duration: Duration::ZERO,
+        }
+    }
+}
+
+/// Command as it would be typed in a shell. Arguments with spaces
+/// or quotes are enclosed in single quotes.
+pub fn to_command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|argument| {
+            let argument = argument.to_string_lossy();
+            if argument.is_empty() || argument.contains([' ', '\t', '"', '\'', '\\', '$', '`']) {
+                format!("'{}'", argument.replace('\'', "'\\''"))
+            } else {
+                argument.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+impl Job {
+    pub fn spawn(command: &mut Command) -> std::io::Result<Job> {
+        let started = Instant::now();
+        let mut child = command.stderr(Stdio::piped()).spawn()?;
+        // Pipe has to be drained while the process is running, otherwise
+        // a verbose compiler blocks as soon as the pipe buffer is full
+        let stderr = child.stderr.take().map(|mut stderr| {
+            std::thread::spawn(move || {
+                let mut output = String::new();
+                let _ = stderr.read_to_string(&mut output);
+                output
+            })
+        });
+        Ok(Job {
+            child,
+            stderr,
+            started,
+        })
+    }
+
+    /// Returns None while the process is still running
+    pub fn try_finish(&mut self) -> Option<JobResult> {
+        let success = match self.child.try_wait() {
+            Ok(Some(status)) => status.success(),
+            Ok(None) => return None,
+            Err(_) => false,
+        };
+        Some(self.collect(success))
+    }
+
+    pub fn finish(mut self) -> JobResult {
+        let success = self
+            .child
+            .wait()
+            .map(|status| status.success())
+            .unwrap_or(false);
+        self.collect(success)
+    }
+
+    fn collect(&mut self, success: bool) -> JobResult {
+        let output = self
+            .stderr
+            .take()
+            .and_then(|handle| handle.join().ok())
+            .unwrap_or_default();
+        JobResult {
+            success,
+            output,
+            duration: self.started.elapsed(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn quoting_command_line() {
+        let mut command = Command::new("c++");
+        command.arg("-DNAME=\"app\"").arg("-c").arg("main file.cpp").arg("it's");
+        assert_eq!(to_command_line(&command), "c++ '-DNAME=\"app\"' -c 'main file.cpp' 'it'\\''s'");
+    }
+}
@@ -0,0 +1,30 @@
+/// Escapes string to be placed between quotes of a JSON document
+pub fn escape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for symbol in value.chars() {
+        match symbol {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            symbol if (symbol as u32) < 0x20 => {
+                result.push_str(&format!("\\u{:04x}", symbol as u32))
+            }
+            symbol => result.push(symbol),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn escaping() {
+        assert_eq!(escape("plain"), "plain");
+        assert_eq!(escape("\"quoted\"\n"), "\\\"quoted\\\"\\n");
+        assert_eq!(escape("C:\\dir"), "C:\\\\dir");
+        assert_eq!(escape("\u{1}"), "\\u0001");
+    }
+}
@@ -0,0 +1,123 @@
+use std::fs;
+
+/// Commit to which a git dependency was resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockedGit {
+    pub name: String,
+    pub url: String,
+    pub rev: String,
+    pub commit: String,
+}
+
+/// Content of `abs.lock` which pins git dependencies to exact commits
+#[derive(Debug, Default)]
+pub struct Lockfile {
+    path: String,
+    locked: Vec<LockedGit>,
+    used: Vec<LockedGit>,
+}
+
+const HEADER: &str = "# This file is generated by abs. It pins commits of git dependencies.\n";
+
+impl Lockfile {
+    pub fn load(path: &str) -> Result<Lockfile, String> {
+        let mut lockfile = Lockfile {
+            path: path.to_string(),
+            ..Default::default()
+        };
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(_) => return Ok(lockfile),
+        };
+        lockfile.locked = Lockfile::parse(&content).map_err(|err| format!("'{path}' is broken: {err}"))?;
+        Ok(lockfile)
+    }
+
+    fn parse(content: &str) -> Result<Vec<LockedGit>, String> {
+        let config: toml::Value = toml::from_str(content).map_err(|err| err.to_string())?;
+        let entries = match config.get("git") {
+            Some(entries) => entries.as_array().ok_or("'git' must be an array of tables")?,
+            None => return Ok(vec![]),
+        };
+        entries
+            .iter()
+            .map(|entry| {
+                let get = |key: &str| {
+                    entry
+                        .get(key)
+                        .and_then(|value| value.as_str())
+                        .map(str::to_string)
+                        .ok_or_else(|| format!("Can't find '{key}' of locked git dependency"))
+                };
+                Ok(LockedGit {
+                    name: get("name")?,
+                    url: get("url")?,
+                    rev: get("rev")?,
+                    commit: get("commit")?,
+                })
+            })
+            .collect()
+    }
+
+    pub fn find(&self, url: &str, rev: &str) -> Option<&LockedGit> {
+        self.locked
+            .iter()
+            .find(|locked| locked.url == url && locked.rev == rev)
+    }
+
+    /// Marks the dependency as used in the current build.
+    /// Entries which weren't used are dropped when the file is saved.
+    pub fn lock(&mut self, locked: LockedGit) {
+        if !self.used.contains(&locked) {
+            self.used.push(locked);
+        }
+    }
+
+    pub fn to_toml(entries: &[LockedGit]) -> String {
+        let entries: Vec<toml::Value> = entries
+            .iter()
+            .map(|locked| {
+                let mut table = toml::value::Table::new();
+                table.insert("name".to_string(), toml::Value::String(locked.name.clone()));
+                table.insert("url".to_string(), toml::Value::String(locked.url.clone()));
+                table.insert("rev".to_string(), toml::Value::String(locked.rev.clone()));
+                table.insert("commit".to_string(), toml::Value::String(locked.commit.clone()));
+                toml::Value::Table(table)
+            })
+            .collect();
+        let mut root = toml::value::Table::new();
+        root.insert("git".to_string(), toml::Value::Array(entries));
+        format!("{HEADER}{}", toml::to_string(&toml::Value::Table(root)).unwrap_or_default())
+    }
+
+    /// Writes the file only when resolved commits differ from the stored ones
+    pub fn save(&self) -> Result<(), String> {
+        let mut used = self.used.clone();
+        used.sort_by(|left, right| (&left.name, &left.url).cmp(&(&right.name, &right.url)));
+        if used == self.locked {
+            return Ok(());
+        }
+        if used.is_empty() {
+            return fs::remove_file(&self.path).map_err(|err| err.to_string());
+        }
+        fs::write(&self.path, Lockfile::to_toml(&used)).map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn lockfile_round_trip() {
+        let entries = vec![LockedGit {
+            name: "foo".to_string(),
+            url: "file:///srv/repos/foo".to_string(),
+            rev: "main".to_string(),
+            commit: "0123456789abcdef0123456789abcdef01234567".to_string(),
+        }];
+        let content = Lockfile::to_toml(&entries);
+        assert!(content.starts_with(HEADER));
+        assert_eq!(Lockfile::parse(&content), Ok(entries));
+        assert_eq!(Lockfile::parse(""), Ok(vec![]));
+    }
+}
@@ -1,14 +1,32 @@
+pub mod cache;
 pub mod file;
+pub mod git;
+pub mod glob;
+pub mod graph;
+pub mod lockfile;
 pub mod profile;
+pub mod progress;
 pub mod profiles_manager;
 pub mod section;
+pub mod target;
 pub mod tank;
+pub mod config;
 pub mod dependency;
+pub mod dependency_cache;
+pub mod diagnostic;
+pub mod job;
+pub mod json;
+pub mod options;
+pub mod package;
+pub mod timings;
+pub mod toolchain;
+pub mod unity;
+pub mod watcher;
+pub mod workspace;
 
 pub mod prelude {
-    pub use super::file::File;
-    pub use super::dependency::Dependency;
-    pub use super::section::Section;
+    pub use super::options::Options;
     pub use super::tank::Tank;
     pub use super::tank::TankError;
+    pub use super::watcher::Watcher;
 }
@@ -0,0 +1,39 @@
+/// Settings passed from the command line which affect every section of the tank
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// Write a report with durations of every spawned process
+    pub timings: bool,
+    /// Triple from `[targets]` table to build for instead of the host
+    pub target: Option<String>,
+    /// Maximum number of compilers running at once. Every section
+    /// of the tank, its dependencies and workspace members share it.
+    pub jobs: Option<usize>,
+    /// Directory for artifacts instead of the one from the configuration file
+    pub build_directory: Option<String>,
+    /// Compile sections in unity batches unless a section turns it off
+    pub unity: bool,
+    /// Program compilers are run through, overrides 'compiler_launcher' settings
+    pub launcher: Option<String>,
+    /// Don't take objects from the cache shared between build directories
+    pub no_cache: bool,
+    /// Print commands instead of running compilers, archiver and linker
+    pub dry_run: bool,
+    /// Print why every translation unit is compiled
+    pub explain: bool,
+    /// Number of `-v` flags, commands are printed from the first one
+    pub verbose: u8,
+    /// Print only errors
+    pub quiet: bool,
+    /// Show a single progress line instead of a line per compiled unit
+    pub progress: bool,
+}
+
+impl Options {
+    pub fn get_jobs(&self) -> usize {
+        self.jobs.filter(|jobs| *jobs > 0).unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|jobs| jobs.get())
+                .unwrap_or(1)
+        })
+    }
+}
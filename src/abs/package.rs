@@ -0,0 +1,59 @@
+use std::process::Command;
+
+/// System library found with pkg-config
+#[allow(unused)]
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    pub requirement: String,
+    pub include_directories: Vec<String>,
+    pub compiling_flags: Vec<String>,
+    pub linking_flags: Vec<String>,
+}
+
+impl Package {
+    /// Resolves requirement like `zlib >= 1.2` by running pkg-config
+    /// (or the program from `PKG_CONFIG` environment variable)
+    pub fn from_pkg_config(name: &str, requirement: &str) -> Result<Package, String> {
+        let program = std::env::var("PKG_CONFIG").unwrap_or_else(|_| "pkg-config".to_string());
+        let module = requirement
+            .split_whitespace()
+            .next()
+            .ok_or_else(|| format!("pkg-config requirement of '{name}' is empty"))?;
+
+        let run = |args: &[&str]| -> Result<String, String> {
+            let output = Command::new(&program)
+                .args(args)
+                .output()
+                .map_err(|err| format!("Can't run '{program}': {err}"))?;
+            if output.status.success() {
+                Ok(String::from_utf8_lossy(&output.stdout).to_string())
+            } else {
+                Err(format!(
+                    "Can't satisfy '{requirement}' for '{name}': {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ))
+            }
+        };
+
+        run(&["--exists", "--print-errors", requirement])?;
+        let cflags = run(&["--cflags", module])?;
+        let libs = run(&["--libs", module])?;
+
+        let (include_directories, compiling_flags): (Vec<String>, Vec<String>) = cflags
+            .split_whitespace()
+            .map(str::to_string)
+            .partition(|flag| flag.starts_with("-I"));
+
+        Ok(Package {
+            name: name.to_string(),
+            requirement: requirement.to_string(),
+            include_directories: include_directories
+                .into_iter()
+                .map(|flag| flag["-I".len()..].to_string())
+                .collect(),
+            compiling_flags,
+            linking_flags: libs.split_whitespace().map(str::to_string).collect(),
+        })
+    }
+}
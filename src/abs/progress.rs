@@ -0,0 +1,72 @@
+use std::io::Write;
+
+use colored::Colorize;
+
+/// Line `[37/120] Compiling foo.cpp` which is redrawn in place while units
+/// are compiled. It's drawn only on terminals, logs get plain lines instead.
+#[derive(Debug)]
+pub struct Progress {
+    total: usize,
+    started: usize,
+    current: String,
+    is_enabled: bool,
+    is_drawn: bool,
+}
+
+impl Progress {
+    pub fn new(total: usize, is_enabled: bool) -> Progress {
+        Progress {
+            total,
+            started: 0,
+            current: String::new(),
+            is_enabled,
+            is_drawn: false,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.is_enabled
+    }
+
+    pub fn start(&mut self, subject: &str) {
+        self.started += 1;
+        self.current = subject.to_string();
+        self.draw();
+    }
+
+    fn draw(&mut self) {
+        if !self.is_enabled {
+            return;
+        }
+        let counter = format!("[{}/{}] ", self.started, self.total);
+        let width = term_size::dimensions_stdout().map(|(width, _)| width).unwrap_or(80);
+        // The last column is left empty, otherwise the terminal wraps the line
+        let available = width.saturating_sub(counter.len() + "Compiling ".len() + 1);
+        let length = self.current.chars().count();
+        let subject: String = if length > available {
+            // End of the path is the most telling part
+            self.current.chars().skip(length - available).collect()
+        } else {
+            self.current.clone()
+        };
+        print!("\r\x1b[K{}{} {}", counter, "Compiling".cyan(), subject);
+        let _ = std::io::stdout().flush();
+        self.is_drawn = true;
+    }
+
+    /// Erases the line, so other output doesn't get mixed with it
+    pub fn clear(&mut self) {
+        if self.is_drawn {
+            print!("\r\x1b[K");
+            let _ = std::io::stdout().flush();
+            self.is_drawn = false;
+        }
+    }
+
+    /// Draws the line again after other output was printed
+    pub fn redraw(&mut self) {
+        if self.started > 0 && self.started <= self.total {
+            self.draw();
+        }
+    }
+}
@@ -1,60 +1,175 @@
 use super::{file::File, profile::Profile};
-use colored::Colorize;
-use std::{collections::HashMap, io::Read, process::Child, rc::Rc, rc::Weak, cell::RefCell, path::Path};
+use super::cache::{CacheKey, ObjectCache};
+use super::config;
+use super::dependency_cache::DependencyCache;
+use super::diagnostic::{Diagnostic, DiagnosticsSummary};
+use super::glob;
+use super::job::{self, Job, JobResult};
+use super::options::Options;
+use super::package::Package;
+use super::progress::Progress;
+use super::timings::Timings;
+use super::toolchain::{CompilerKind, Toolchain};
+use super::unity::{self, Unity};
+use colored::{ColoredString, Colorize};
+use std::{collections::HashMap, rc::Rc, rc::Weak, cell::RefCell, path::Path};
 use super::tank::Tank;
 
+#[allow(unused)]
 #[derive(Debug)]
 pub enum SectionError {
     MandatoryLack(String),
     FieldTypeError(String),
+    PipeNotFound(String),
+    SourceNotFound(String),
 }
 
 #[allow(unused)]
 #[derive(Debug)]
 pub struct Section {
     pub name: String,
+    /// Name of the tank the section belongs to
+    tank_name: String,
     pub outlet_type: String,
     profile: Rc<Profile>,
+    options: Rc<Options>,
+    toolchain: Rc<Toolchain>,
+    build_directory: String,
     pipes: Vec<Weak<RefCell<Section>>>,
+    packages: Vec<Rc<Package>>,
     files: Vec<File>,
     include_directories: Vec<String>,
+    /// Include directories propagated to the sections which pipe this one
+    public_include_directories: Vec<String>,
+    /// Flags used for this section, including the public ones of piped sections
+    flags: Flags,
+    /// Flags propagated to the sections which pipe this one
+    public_flags: Flags,
+    precompiled_header: Option<File>,
+    unity: Option<Unity>,
+    cache: Option<ObjectCache>,
+    /// Canonical directory of the configuration file
+    root: String,
+    library_directories: Vec<String>,
     sources_of_dependency: HashMap<File, Vec<File>>,
     dependencies_of_source: HashMap<File, Vec<File>>,
+    dependency_cache: DependencyCache,
+}
+
+/// Defines and options set in the section table
+#[derive(Debug, Default, Clone)]
+pub struct Flags {
+    pub defines: Vec<String>,
+    pub options: Vec<String>,
+    pub linking_options: Vec<String>,
+}
+
+impl Flags {
+    fn get_mut(&mut self, kind: &str) -> &mut Vec<String> {
+        match kind {
+            "defines" => &mut self.defines,
+            "options" => &mut self.options,
+            _ => &mut self.linking_options,
+        }
+    }
+
+    fn extend(&mut self, other: &Flags) {
+        for (flags, others) in [
+            (&mut self.defines, &other.defines),
+            (&mut self.options, &other.options),
+            (&mut self.linking_options, &other.linking_options),
+        ] {
+            for flag in others {
+                if !flags.contains(flag) {
+                    flags.push(flag.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Input of a single compiler process: a source or a unity file including several ones
+#[derive(Debug)]
+struct CompilationUnit<'a> {
+    input: String,
+    object: String,
+    sources: Vec<&'a File>,
 }
 
 pub const RESULT_BORDER_WIDTH: usize = 10;
 
+/// Reason given by `--explain` for a unit without an object
+const NOT_BUILT_REASON: &str = "not built yet";
+
 #[allow(unused)]
 impl Section {
     pub fn new(tank: &Tank, name: String, config: &toml::Value, profile: Rc<Profile>) -> Result<Section, SectionError> {
-        std::fs::create_dir_all(format!(".abs/{}", name));
-
-        let source_dir = config
-            .get("source")
-            .ok_or(SectionError::MandatoryLack(
-                "'source' is mandatory field!".to_string(),
-            ))?
-            .as_str()
-            .ok_or(SectionError::FieldTypeError(
-                "'source' is string type!".to_string(),
-            ))?;
-
-        let mut section_files = File::collect_files(source_dir, [".hpp", ".cpp", ".h", ".c"]);
+        let is_dry_run = tank.get_options().dry_run;
+        if !is_dry_run {
+            std::fs::create_dir_all(format!("{}/{}", tank.get_build_directory(), name));
+        }
 
-        let mut include_directories = Section::collect_default_includes();
-        include_directories.push(source_dir.to_string());
+        let mut section_files = vec![];
+        let mut include_directories = tank.get_toolchain().probe.include_directories.clone();
 
-        if let Some(include_dir) = config.get("include") {
-            let include_dir = include_dir
+        if let Some(source_dir) = config.get("source") {
+            let source_dir = tank.resolve_path(source_dir
                 .as_str()
                 .ok_or(SectionError::FieldTypeError(
                     "'source' is string type!".to_string(),
-                ))?;
-            section_files.append(&mut File::collect_files(
-                include_dir,
-                [".hpp", ".cpp", ".h", ".c"],
+                ))?);
+            section_files.append(&mut File::collect_files(&source_dir, [".hpp", ".cpp", ".h", ".c"]));
+            include_directories.push(source_dir);
+        }
+        if config.get("sources").is_some() {
+            section_files.append(&mut Section::collect_sources(tank, config)?);
+        }
+        if config.get("source").is_none() && config.get("sources").is_none() {
+            return Err(SectionError::MandatoryLack(
+                "'source' or 'sources' is mandatory field!".to_string(),
             ));
-            include_directories.push(include_dir.to_string());
+        }
+        include_directories.extend(profile.include_directories.iter().map(|directory| tank.resolve_path(directory)));
+
+        // Plain 'include' is public, so sections piping this one see it as well
+        let mut public_include_directories = vec![];
+        for (key, is_public) in [("include", true), ("public_include", true), ("private_include", false)] {
+            let directories = config::get_strings(config, key)
+                .map_err(SectionError::FieldTypeError)?
+                .unwrap_or_default();
+            for directory in directories {
+                let directory = tank.resolve_path(&directory);
+                if is_public {
+                    public_include_directories.push(directory.clone());
+                }
+                include_directories.push(directory);
+            }
+        }
+
+        // Unlike include directories plain flags are private
+        let mut flags = Flags::default();
+        let mut public_flags = Flags::default();
+        for kind in ["defines", "options", "linking_options"] {
+            for (key, is_public) in [
+                (kind.to_string(), false),
+                (format!("private_{kind}"), false),
+                (format!("public_{kind}"), true),
+            ] {
+                let values = config::get_strings(config, &key)
+                    .map_err(SectionError::FieldTypeError)?
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|value| match kind {
+                        "defines" if !value.starts_with("-D") => format!("-D{value}"),
+                        _ => value,
+                    });
+                for value in values {
+                    if is_public {
+                        public_flags.get_mut(kind).push(value.clone());
+                    }
+                    flags.get_mut(kind).push(value);
+                }
+            }
         }
 
         let mut outlet_type = String::from("executable");
@@ -70,117 +185,435 @@ impl Section {
             }
         }
 
-        let sources_of_dependency = 
-            Section::create_map_dependency_sources(&section_files, &include_directories);
-        let dependencies_of_source =
-            Section::create_map_source_dependencies(&section_files, &include_directories);
-    
         let mut pipes = vec![];
+        let mut packages: Vec<Rc<Package>> = vec![];
         if let Some(value) = config.get("pipes") {
-            if let Some(value ) = value.as_array()
-            {
-                pipes = value
-                    .iter()
-                    .map(|elem| {
-                        if elem.is_str() {
-                            let name = elem.as_str().unwrap().split(".").collect::<Vec<&str>>()[1];
-
-                            return Weak::clone(tank.get_sections()
-                                .iter()
-                                .find(|section| {
-                                    if let Some(section) = section.upgrade() {
-                                        let section = section.borrow_mut();
-                                        return section.name == name;
-                                    } else {
-                                        return false;
-                                    }
-                                })
-                                .unwrap()); // todo doesn't exist case
-                        } else {
-                            println!(
-                            "{:>RESULT_BORDER_WIDTH$}",
-                            " Wrong type for pipe".bright_red()
-                        );
-                        std::process::exit(1);
-                    }})
-                    .collect()
-            } else {
-                return Err(SectionError::FieldTypeError("pipes must be an array".to_string()));
+            let value = value
+                .as_array()
+                .ok_or(SectionError::FieldTypeError("pipes must be an array".to_string()))?;
+            for elem in value {
+                let pipe = elem
+                    .as_str()
+                    .ok_or(SectionError::FieldTypeError("pipe is string type".to_string()))?;
+                match pipe.split('.').collect::<Vec<&str>>().as_slice() {
+                    ["sections", section_name] => pipes.push(
+                        tank.find_section(section_name)
+                            .ok_or(SectionError::PipeNotFound(pipe.to_string()))?,
+                    ),
+                    [dependency, "sections", section_name] => pipes.push(
+                        tank.find_dependency_section(dependency, section_name)
+                            .ok_or(SectionError::PipeNotFound(pipe.to_string()))?,
+                    ),
+                    [package_name] => packages.push(
+                        tank.get_package(package_name)
+                            .ok_or(SectionError::PipeNotFound(pipe.to_string()))?,
+                    ),
+                    _ => return Err(SectionError::PipeNotFound(pipe.to_string())),
+                }
             }
         }
+        for package in &packages {
+            include_directories.extend(package.include_directories.iter().cloned());
+        }
+        // Public include directories of piped sections already contain the ones they pipe
+        for pipe in pipes.iter().filter_map(Weak::upgrade) {
+            let pipe = pipe.borrow();
+            flags.extend(&pipe.public_flags);
+            public_flags.extend(&pipe.public_flags);
+            for directory in &pipe.public_include_directories {
+                if !public_include_directories.contains(directory) {
+                    public_include_directories.push(directory.clone());
+                }
+                if !include_directories.contains(directory) {
+                    include_directories.push(directory.clone());
+                }
+            }
+        }
+
+        // Header is tracked like the other files so changes of it or of
+        // the headers it includes invalidate the precompiled one
+        let precompiled_header = match config.get("precompiled_header") {
+            Some(value) => {
+                let path = tank.resolve_path(value.as_str().ok_or(SectionError::FieldTypeError(
+                    "'precompiled_header' is string type!".to_string(),
+                ))?);
+                let full_path = Path::new(&path)
+                    .canonicalize()
+                    .map_err(|err| SectionError::SourceNotFound(format!("{path}: {err}")))?;
+                let header = File::new(&full_path.to_string_lossy())
+                    .map_err(|err| SectionError::SourceNotFound(format!("{path}: {:?}", err)))?;
+                section_files.push(header.clone());
+                Some(header)
+            }
+            None => None,
+        };
+
+        section_files.sort_by(|left, right| left.path.cmp(&right.path));
+        section_files.dedup_by(|left, right| left.path == right.path);
+
+        let library_directories = profile
+            .library_directories
+            .iter()
+            .map(|directory| tank.resolve_path(directory))
+            .collect();
+
+        let mut dependency_cache = DependencyCache::load(
+            &format!("{}/{}/dependencies.toml", tank.get_build_directory(), name),
+            &include_directories,
+        );
+        let dependencies_of_source = Section::create_map_source_dependencies(
+            &section_files,
+            &tank.get_toolchain().probe.include_directories,
+            &mut dependency_cache,
+        );
+        if !is_dry_run {
+            dependency_cache.save(&dependencies_of_source.keys().cloned().collect::<Vec<File>>());
+        }
+        let sources_of_dependency = Section::create_map_dependency_sources(&dependencies_of_source);
+
+        let unity_settings = Section::get_unity_settings(tank, config, &section_files)?;
 
-        Ok(Section {
+        let mut section = Section {
             name,
             outlet_type,
             profile,
+            options: tank.get_options(),
+            toolchain: tank.get_toolchain(),
+            build_directory: tank.get_build_directory().to_string(),
             pipes,
+            packages,
             files: section_files,
             include_directories,
+            public_include_directories,
+            flags,
+            public_flags,
+            precompiled_header,
+            unity: None,
+            cache: if tank.get_options().no_cache || !tank.get_toolchain().cache {
+                None
+            } else {
+                Some(ObjectCache::new())
+            },
+            tank_name: tank.get_name().to_string(),
+            root: Path::new(tank.get_root())
+                .canonicalize()
+                .map(|root| root.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            library_directories,
             sources_of_dependency,
             dependencies_of_source,
-        })
+            dependency_cache,
+        };
+        if let Some((sources, batch_size)) = unity_settings {
+            let directory = format!("{}/unity", section.get_artifacts_path());
+            section.unity = Some(Unity::new(&sources, batch_size, &directory));
+        }
+        Ok(section)
     }
 
-    fn collect_default_includes() -> Vec<String> {
-        let includes = std::process::Command::new("sh")
-            .arg("-c")
-            .arg("c++ -xc++ /dev/null -E -Wp,-v 2>&1 | sed -n 's,^ ,,p'")
-            .output()
-            .expect("failed to execute process");
-
-        let includes = String::from_utf8(includes.stdout).unwrap();
-        let includes: Vec<String> = includes
-            .split("\n")
-            .map(str::to_string)
-            .filter(|str| !str.is_empty())
+    /// C++ sources compiled in unity batches and the size of a batch or None when
+    /// unity build is off. Sources matching 'unity_exclude' are compiled one by one.
+    fn get_unity_settings(
+        tank: &Tank,
+        config: &toml::Value,
+        files: &[File],
+    ) -> Result<Option<(Vec<File>, usize)>, SectionError> {
+        let is_enabled = match config.get("unity") {
+            Some(value) => value.as_bool().ok_or(SectionError::FieldTypeError(
+                "'unity' is boolean type!".to_string(),
+            ))?,
+            None => tank.get_options().unity,
+        };
+        if !is_enabled {
+            return Ok(None);
+        }
+        let batch_size = match config.get("unity_batch_size") {
+            Some(value) => value
+                .as_integer()
+                .filter(|size| *size > 0)
+                .ok_or(SectionError::FieldTypeError(
+                    "'unity_batch_size' is positive integer!".to_string(),
+                ))? as usize,
+            None => unity::DEFAULT_BATCH_SIZE,
+        };
+        let excluded = config::get_strings(config, "unity_exclude")
+            .map_err(SectionError::FieldTypeError)?
+            .unwrap_or_default();
+        let root = Path::new(tank.get_root())
+            .canonicalize()
+            .map_err(|err| SectionError::SourceNotFound(err.to_string()))?;
+        let sources = files
+            .iter()
+            .filter(|file| file.path.ends_with(".cpp"))
+            .filter(|file| {
+                let relative = Path::new(&file.path)
+                    .strip_prefix(&root)
+                    .map(|path| path.to_string_lossy().to_string())
+                    .unwrap_or_else(|_| file.path.clone());
+                !excluded
+                    .iter()
+                    .any(|pattern| glob::matches_path_or_parent(pattern, &relative))
+            })
+            .cloned()
             .collect();
-        includes
+        Ok(Some((sources, batch_size)))
+    }
+
+    /// Files from `sources`: directories, explicit files and glob patterns
+    /// relative to the configuration file. Entries starting with '!' exclude
+    /// matching files and everything inside of matching directories.
+    fn collect_sources(tank: &Tank, config: &toml::Value) -> Result<Vec<File>, SectionError> {
+        let entries = config::get_strings(config, "sources")
+            .map_err(SectionError::FieldTypeError)?
+            .unwrap_or_default();
+        let (excluded, included): (Vec<&String>, Vec<&String>) =
+            entries.iter().partition(|entry| entry.starts_with('!'));
+
+        let mut paths = vec![];
+        for entry in included {
+            if glob::is_pattern(entry) {
+                paths.append(&mut glob::expand(tank.get_root(), entry));
+            } else if Path::new(&tank.resolve_path(entry)).is_dir() {
+                paths.extend(
+                    glob::list_files(tank.get_root(), entry)
+                        .into_iter()
+                        .filter(|path| [".hpp", ".cpp", ".h", ".c"].iter().any(|suffix| path.ends_with(suffix))),
+                );
+            } else if Path::new(&tank.resolve_path(entry)).is_file() {
+                paths.push(entry.to_string());
+            } else {
+                return Err(SectionError::SourceNotFound(entry.to_string()));
+            }
+        }
+        paths.retain(|path| {
+            !excluded
+                .iter()
+                .any(|pattern| glob::matches_path_or_parent(&pattern[1..], path))
+        });
+
+        paths
+            .iter()
+            .map(|path| {
+                let full_path = Path::new(&tank.resolve_path(path))
+                    .canonicalize()
+                    .map_err(|err| SectionError::SourceNotFound(format!("{path}: {err}")))?;
+                File::new(&full_path.to_string_lossy())
+                    .map_err(|err| SectionError::SourceNotFound(format!("{path}: {:?}", err)))
+            })
+            .collect()
     }
 
+    /// Standard from profile is a C++ one so it's skipped for C sources
+    fn get_standard_for(&self, file: &File) -> Option<&String> {
+        if file.path.ends_with(".c") {
+            None
+        } else {
+            Some(&self.profile.standard)
+        }
+    }
+
+    /// Direct includes of section files and of every header they reach, each file is
+    /// listed among its own dependencies. Headers in the system directories aren't scanned.
     fn create_map_source_dependencies(
-        paths: &Vec<File>,
-        search_list: &Vec<String>,
+        paths: &[File],
+        system_directories: &[String],
+        cache: &mut DependencyCache,
     ) -> HashMap<File, Vec<File>> {
         let mut map: HashMap<File, Vec<File>> = HashMap::new();
+        let mut headers: Vec<File> = vec![];
         for path in paths {
-            map.insert(
-                path.to_owned(),
-                path.collect_dependencies(search_list)
-                    .into_iter()
-                    .chain(vec![path.to_owned()].into_iter())
-                    .collect(),
-            );
+            let dependencies = cache.get_dependencies(path);
+            headers.extend(dependencies.iter().cloned());
+            map.insert(path.to_owned(), dependencies.into_iter().chain(vec![path.to_owned()]).collect());
         }
-        return map;
+        while let Some(header) = headers.pop() {
+            if map.contains_key(&header)
+                || system_directories
+                    .iter()
+                    .any(|directory| header.path.starts_with(directory.as_str()))
+            {
+                continue;
+            }
+            let dependencies = cache.get_header_dependencies(&header);
+            headers.extend(dependencies.iter().cloned());
+            map.insert(header.clone(), dependencies.into_iter().chain(vec![header]).collect());
+        }
+        map
     }
 
     fn create_map_dependency_sources(
-        paths: &Vec<File>,
-        search_list: &Vec<String>,
+        dependencies_of_source: &HashMap<File, Vec<File>>,
     ) -> HashMap<File, Vec<File>> {
         let mut map: HashMap<File, Vec<File>> = HashMap::new();
-        let src_dep = Section::create_map_source_dependencies(paths, search_list);
-        for (source, dependencies) in src_dep.iter() {
+        for (source, dependencies) in dependencies_of_source {
             for dependency in dependencies {
-                if map.contains_key(dependency) {
-                    map.get_mut(dependency).unwrap().push(source.to_owned());
-                } else {
-                    map.insert(dependency.to_owned(), vec![source.to_owned()]);
+                map.entry(dependency.to_owned())
+                    .or_default()
+                    .push(source.to_owned());
+            }
+        }
+        map
+    }
+
+    /// Sources and headers they include, ordered by path so files are always processed in the same order
+    fn get_tracked_files(&self) -> Vec<File> {
+        let mut files: Vec<File> = self.sources_of_dependency.keys().cloned().collect();
+        files.sort_by(|left, right| left.path.cmp(&right.path));
+        files
+    }
+
+    /// Canonical paths of the files the section is built from
+    pub fn get_watched_files(&self) -> Vec<String> {
+        self.get_tracked_files()
+            .iter()
+            .chain(self.files.iter())
+            .filter(|file| !self.is_system_file(file))
+            .filter_map(|file| Path::new(&file.path).canonicalize().ok())
+            .map(|path| path.to_string_lossy().to_string())
+            .collect()
+    }
+
+    /// Takes new modification times of the changed files and rescans includes
+    /// of the changed files. Returns false when a file disappeared.
+    pub fn refresh(&mut self, changed: &[String]) -> bool {
+        let mut updated: HashMap<String, File> = HashMap::new();
+        for file in self.get_tracked_files().iter().chain(self.files.iter()) {
+            let is_changed = Path::new(&file.path)
+                .canonicalize()
+                .map(|path| changed.contains(&path.to_string_lossy().to_string()))
+                .unwrap_or(true);
+            if !is_changed || updated.contains_key(&file.path) {
+                continue;
+            }
+            match File::new(&file.path) {
+                Ok(refreshed) => updated.insert(file.path.clone(), refreshed),
+                Err(_) => return false,
+            };
+            // Freeze time has a precision of a second, a change within it would be missed
+            std::fs::remove_file(file.get_freeze_path_in(&self.get_artifacts_path()));
+        }
+        if updated.is_empty() {
+            return true;
+        }
+        let update = |file: &File| updated.get(&file.path).cloned().unwrap_or_else(|| file.clone());
+        self.files = self.files.iter().map(update).collect();
+        self.precompiled_header = self.precompiled_header.as_ref().map(update);
+        if let Some(unity) = &mut self.unity {
+            unity.update(update);
+        }
+        // Includes of unchanged files are taken from the cache
+        let dependencies_of_source = Section::create_map_source_dependencies(
+            &self.files,
+            &self.toolchain.probe.include_directories,
+            &mut self.dependency_cache,
+        );
+        self.dependency_cache
+            .save(&dependencies_of_source.keys().cloned().collect::<Vec<File>>());
+        let sources_of_dependency = Section::create_map_dependency_sources(&dependencies_of_source);
+        self.dependencies_of_source = dependencies_of_source;
+        self.sources_of_dependency = sources_of_dependency;
+        true
+    }
+
+    /// Path relative to the root of the tank when the file is inside of it
+    fn get_display_path(&self, file: &File) -> String {
+        let path = Path::new(&file.path)
+            .canonicalize()
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_else(|_| file.path.clone());
+        self.shorten_path(&path)
+    }
+
+    /// Paths are kept absolute and shortened only when they are printed
+    fn shorten_path(&self, path: &str) -> String {
+        match path.strip_prefix(&format!("{}/", self.root)) {
+            Some(relative) => relative.to_string(),
+            None => path.to_string(),
+        }
+    }
+
+    /// Name prefixed with the tank, sections of different members or dependencies can share names
+    pub fn get_qualified_name(&self) -> String {
+        format!("{}:{}", self.tank_name, self.name)
+    }
+
+    pub fn get_pipe_names(&self) -> Vec<String> {
+        self.pipes
+            .iter()
+            .filter_map(Weak::upgrade)
+            .map(|pipe| pipe.borrow().get_qualified_name())
+            .collect()
+    }
+
+    /// Header of the compiler or of the system, found in the directories reported by the compiler
+    fn is_system_file(&self, file: &File) -> bool {
+        self.toolchain
+            .probe
+            .include_directories
+            .iter()
+            .any(|directory| file.path.starts_with(directory.as_str()))
+    }
+
+    /// Files with headers they include, headers of the toolchain are skipped
+    pub fn get_include_edges(&self) -> Vec<(String, String)> {
+        let mut edges = vec![];
+        for source in self.get_tracked_files() {
+            let mut dependencies: Vec<&File> = self
+                .dependencies_of_source
+                .get(&source)
+                .map(|dependencies| dependencies.iter().collect())
+                .unwrap_or_default();
+            dependencies.sort_by(|left, right| left.path.cmp(&right.path));
+            for dependency in dependencies {
+                if *dependency != source && !self.is_system_file(dependency) {
+                    edges.push((self.get_display_path(&source), self.get_display_path(dependency)));
+                }
+            }
+        }
+        edges
+    }
+
+    /// Files which include the file directly or through other headers, the file itself included
+    fn get_dependent_files<'a>(&'a self, file: &'a File) -> Vec<&'a File> {
+        let mut visited = vec![file];
+        let mut index = 0;
+        while index < visited.len() {
+            for source in self.sources_of_dependency.get(visited[index]).into_iter().flatten() {
+                if !visited.contains(&source) {
+                    visited.push(source);
                 }
             }
+            index += 1;
         }
-        return map;
+        visited
     }
 
-    fn get_modified(&self, files: &Vec<File>) -> Vec<File> {
-        let mut files = files.clone();
-        files.retain(|file| file.is_modified_in(&format!("{}/{}", &self.name, &self.profile.name)));
-        return files;
+    /// Translation units compiled again after the header changes, including the ones
+    /// which include it through other headers. None when the section doesn't use the header.
+    pub fn get_rebuilt_units(&self, header: &Path) -> Option<Vec<String>> {
+        let header = self
+            .sources_of_dependency
+            .keys()
+            .find(|file| Path::new(&file.path).canonicalize().ok().as_deref() == Some(header))?;
+        let mut units: Vec<String> = self
+            .get_dependent_files(header)
+            .into_iter()
+            .filter(|file| file.get_object_path_in(&self.get_artifacts_path()).is_ok())
+            .map(|file| self.get_display_path(file))
+            .collect();
+        units.sort();
+        Some(units)
+    }
+
+    fn get_modified(&self, files: &[File]) -> Vec<File> {
+        let mut files = files.to_vec();
+        files.retain(|file| file.is_modified_in(&self.get_artifacts_path()));
+        files
     }
 
     fn freeze(&self, file: &File) {
         std::fs::create_dir_all(self.get_frozen_path());
-        let f = std::fs::File::create(file.get_freeze_path_in(&format!("{}/{}", &self.name, &self.profile.name)))
+        let f = std::fs::File::create(file.get_freeze_path_in(&self.get_artifacts_path()))
             .expect("Unable to create file");
         let mut f = std::io::BufWriter::new(f);
         let now = chrono::Local::now()
@@ -194,20 +627,29 @@ impl Section {
         Path::new(&self.get_outlet_path()).exists()
     }
 
+    /// Directory where artifacts of the current target and profile are placed.
+    /// Host builds aren't namespaced by target.
+    pub fn get_artifacts_path(&self) -> String {
+        match &self.toolchain.target {
+            Some(target) => format!("{}/{}/{}/{}", self.build_directory, self.name, target.triple, self.profile.name),
+            None => format!("{}/{}/{}", self.build_directory, self.name, self.profile.name),
+        }
+    }
+
     pub fn get_outlet_path(&self) -> String {
         if self.outlet_type == "executable"{
             format!(
-                ".abs/{}/{}/{}",
-                self.name, self.profile.name, self.name)
+                "{}/{}",
+                self.get_artifacts_path(), self.name)
         } else if self.outlet_type == "library" {
             format!(
-                ".abs/{}/{}/lib{}.a",
-                self.name, self.profile.name, self.name
+                "{}/lib{}.a",
+                self.get_artifacts_path(), self.name
             )
         } else if self.outlet_type == "shared" {
             format!(
-                ".abs/{}/{}/lib{}.so",
-                self.name, self.profile.name, self.name
+                "{}/lib{}.so",
+                self.get_artifacts_path(), self.name
             )
         } else {
             panic!("unexpected outlet type {}", self.outlet_type);
@@ -215,100 +657,270 @@ impl Section {
     }
 
     pub fn get_binary_path(&self) -> String {
-        format!(".abs/{}/{}/binary/", self.name, self.profile.name)
+        format!("{}/binary/", self.get_artifacts_path())
     }
     pub fn get_frozen_path(&self) -> String {
-        format!(".abs/{}/{}/frozen/", self.name, self.profile.name)
+        format!("{}/frozen/", self.get_artifacts_path())
     }
 }
 
 #[allow(unused)]
 impl Section {
+    /// Successful jobs aren't printed in quiet mode, even with warnings
+    fn print_job_result(&self, status: ColoredString, subject: &str, result: &JobResult) {
+        if self.options.quiet && result.success {
+            return;
+        }
+        let mut message = format!("{:>RESULT_BORDER_WIDTH$} {}", status, subject);
+        if !result.output.is_empty() {
+            message.push('\n');
+            message.push_str(result.output.trim_end());
+        }
+        println!("{message}");
+    }
+
+    fn print_diagnostics_summary(&self, summary: &DiagnosticsSummary) {
+        if summary.is_empty() || (self.options.quiet && summary.errors == 0) {
+            return;
+        }
+        let summary = if summary.errors > 0 {
+            summary.to_string().red().bold()
+        } else {
+            summary.to_string().yellow().bold()
+        };
+        println!(
+            "{:>RESULT_BORDER_WIDTH$} '{}': {}",
+            "Summary".cyan(),
+            self.name,
+            summary
+        );
+    }
+
+    /// Passes results of finished jobs to the handler and drops them from the list
+    fn poll_jobs(
+        children: &mut Vec<(&CompilationUnit, Option<String>, Job)>,
+        progress: &mut Progress,
+        handle_result: &mut impl FnMut(JobResult, &CompilationUnit, &Option<String>, &mut Progress),
+    ) {
+        children.retain_mut(|(unit, key, job)| match job.try_finish() {
+            Some(result) => {
+                handle_result(result, unit, key, progress);
+                false
+            }
+            None => true,
+        });
+    }
+
+    /// Prints the command with its working directory in verbose mode before spawning it
+    fn spawn_job(&self, command: &mut std::process::Command) -> std::io::Result<Job> {
+        self.print_command(command);
+        Job::spawn(command).map_err(|err| {
+            let program = command.get_program().to_string_lossy().to_string();
+            std::io::Error::new(err.kind(), format!("Can't run '{program}': {err}"))
+        })
+    }
+
+    fn print_command(&self, command: &std::process::Command) {
+        if self.options.verbose > 0 {
+            let directory = command
+                .get_current_dir()
+                .map(Path::to_path_buf)
+                .or_else(|| std::env::current_dir().ok())
+                .map(|directory| directory.to_string_lossy().to_string())
+                .unwrap_or_default();
+            println!(
+                "{:>RESULT_BORDER_WIDTH$} in '{}': {}",
+                "Command".cyan(),
+                directory,
+                job::to_command_line(command)
+            );
+        }
+    }
+
+    /// Compiler with every flag needed to compile the file except input and output
+    fn get_compiling_command(&self, file: &File) -> std::process::Command {
+        let mut command = self.toolchain.get_command(self.toolchain.get_compiler_for(file));
+        command
+            .args(self.toolchain.get_compiling_flags())
+            .args(&self.profile.options)
+            .args(self.get_standard_for(file))
+            .args(&self.profile.defines)
+            .args(&self.flags.options)
+            .args(&self.flags.defines)
+            .args(self.get_package_compiling_flags())
+            .args(self.include_directories.iter().map(|directory| format!("-I{directory}")))
+            .args(self.get_precompiled_header_flags(file));
+        command
+    }
+
+    /// Key of the object built by the command or None when caching is off. Headers reached
+    /// through the dependency map are hashed, the system ones are covered by the compiler version.
+    fn get_object_cache_key(&self, unit: &CompilationUnit, command: &std::process::Command) -> Option<String> {
+        self.cache.as_ref()?;
+        let mut key = CacheKey::new("object", &self.root);
+        key.add_text(self.toolchain.probe.kind.as_str());
+        key.add_text(&self.toolchain.probe.version);
+        key.add_text(&self.toolchain.probe.target);
+        key.add_text(command.get_program().to_str()?);
+        for argument in command.get_args() {
+            key.add_text(argument.to_str()?);
+        }
+        // Batch of a unity build isn't in the map, its sources are
+        if !key.add_file(&unit.input) {
+            return None;
+        }
+        let mut pending: Vec<&File> = unit.sources.iter().copied().chain(self.precompiled_header.as_ref()).collect();
+        let mut files: Vec<&File> = vec![];
+        while let Some(file) = pending.pop() {
+            if files.contains(&file) || self.is_system_file(file) {
+                continue;
+            }
+            files.push(file);
+            if let Some(dependencies) = self.dependencies_of_source.get(file) {
+                pending.extend(dependencies.iter());
+            }
+        }
+        files.sort_by(|left, right| left.path.cmp(&right.path));
+        for file in files {
+            if !key.add_file(&file.path) {
+                return None;
+            }
+        }
+        Some(key.finish())
+    }
+
+    /// Header which includes the configured one. It's placed next to the
+    /// precompiled header, so GCC picks the latter up by `-include`.
+    fn get_precompiled_header_stub_path(&self, header: &File) -> String {
+        let name = Path::new(&header.path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        format!("{}/pch/{}", self.get_artifacts_path(), name)
+    }
+
+    fn get_precompiled_header_output_path(&self, header: &File) -> String {
+        let extension = if self.toolchain.probe.kind == CompilerKind::Clang {
+            "pch"
+        } else {
+            "gch"
+        };
+        format!("{}.{}", self.get_precompiled_header_stub_path(header), extension)
+    }
+
+    /// Until the header is precompiled it is included as is. A dry run prints the precompiling
+    /// whenever the output is missing, so the commands after it use the output as the build would.
+    fn get_precompiled_header_flags(&self, file: &File) -> Vec<String> {
+        let header = match &self.precompiled_header {
+            Some(header) if header != file && !file.path.ends_with(".c") => header,
+            _ => return vec![],
+        };
+        let output = self.get_precompiled_header_output_path(header);
+        if !Path::new(&output).exists() && !self.options.dry_run {
+            vec!["-include".to_string(), header.path.clone()]
+        } else if self.toolchain.probe.kind == CompilerKind::Clang {
+            vec!["-include-pch".to_string(), output]
+        } else {
+            vec!["-include".to_string(), self.get_precompiled_header_stub_path(header)]
+        }
+    }
+
+    fn is_precompiled_header_outdated(&self, header: &File, modified: &[File]) -> bool {
+        !Path::new(&self.get_precompiled_header_output_path(header)).exists()
+            || modified
+                .iter()
+                .any(|file| self.get_dependent_files(file).contains(&header))
+    }
+
+    fn precompile_header(&self, header: &File, timings: &mut Timings) -> bool {
+        let stub = self.get_precompiled_header_stub_path(header);
+        let output = self.get_precompiled_header_output_path(header);
+        let mut command = self.get_compiling_command(header);
+        command
+            .arg("-x")
+            .arg("c++-header")
+            .arg(&stub)
+            .arg("-o")
+            .arg(&output);
+        if self.options.dry_run {
+            println!("{}", job::to_command_line(&command));
+            return true;
+        }
+        std::fs::create_dir_all(format!("{}/pch", self.get_artifacts_path()));
+        std::fs::remove_file(&output);
+        if let Err(err) = std::fs::write(&stub, format!("#include \"{}\"\n", header.path)) {
+            println!(
+                "{:>RESULT_BORDER_WIDTH$} to write '{}': {}",
+                "Failed".red().bold(),
+                stub,
+                err
+            );
+            return false;
+        }
+        let result = match self.spawn_job(&mut command) {
+            Ok(job) => job.finish(),
+            Err(err) => JobResult::from_spawn_error(err),
+        };
+        timings.record_compiling(&self.shorten_path(&header.path), result.duration);
+        if result.success {
+            self.print_job_result("Complete".green().bold(), &format!("'{}' precompiling", self.shorten_path(&header.path)), &result);
+        } else {
+            self.print_job_result("Fail".red().bold(), &format!("'{}' precompiling", self.shorten_path(&header.path)), &result);
+            std::fs::remove_file(&output);
+        }
+        result.success
+    }
+
     pub fn check(&self) -> bool {
-        let mut modified = self.get_modified(&self.sources_of_dependency.keys().cloned().collect());
+        let mut modified = self.get_modified(&self.get_tracked_files());
         modified.append(&mut self.collect_missing_objects());
 
         if modified.is_empty() && self.check_is_outlet_exist() {
-            println!(
-                "{:>RESULT_BORDER_WIDTH$} {}",
-                "Checking".bright_green(),
-                "everything is ok"
-            );
+            if !self.options.quiet {
+                println!(
+                    "{:>RESULT_BORDER_WIDTH$} everything is ok",
+                    "Checking".bright_green()
+                );
+            }
             return true;
         }
 
         let mut is_successful = true;
         let mut built: Vec<String> = vec![];
         let mut compiled_number = 0;
+        let mut summary = DiagnosticsSummary::default();
 
         for modified_file in &modified {
-            for for_build in &self.sources_of_dependency[modified_file] {
+            for for_build in self.get_dependent_files(modified_file) {
                 if built.contains(&for_build.path)
                     || for_build.path.ends_with(".hpp")
                     || for_build.path.ends_with(".h")
                 {
                     continue;
                 }
-                let included_directories_argument = self
-                    .include_directories
-                    .iter()
-                    .map(|str| format!("-I{}", str));
-
-                let mut child = std::process::Command::new("g++")
-                    .args(&self.profile.options)
-                    .arg(&self.profile.standard)
-                    .args(&self.profile.defines)
-                    .args(included_directories_argument)
-                    .arg("-fsyntax-only")
-                    .arg(&for_build.path)
-                    .spawn()
-                    .unwrap();
-
-                let mut output = String::new();
-
-                match child.wait() {
-                    Ok(exit_status) => {
-                        let stderr = child.stdout;
-                        if stderr.is_some() {
-                            let mut reader = std::io::BufReader::new(stderr.unwrap());
-                            reader.read_to_string(&mut output);
-                        }
-
-                        if exit_status.success() {
-                            println!(
-                                "{:>RESULT_BORDER_WIDTH$} '{}'",
-                                "Ok".green().bold(),
-                                for_build.path
-                            );
-                            compiled_number += 1;
-                        } else {
-                            println!(
-                                "{:>RESULT_BORDER_WIDTH$} '{}'",
-                                "Fail".red().bold(),
-                                for_build.path
-                            );
-                            if !output.is_empty() {
-                                println!("{}", output);
-                            }
-                            is_successful = false;
-                            built.push(for_build.path.clone());
-                            continue;
-                        }
-                    }
-                    Err(_) => {
-                        println!(
-                            "{:>RESULT_BORDER_WIDTH$} '{}'",
-                            "Fail".red().bold(),
-                            for_build.path
-                        );
-                        is_successful = false;
-                        built.push(for_build.path.clone());
-                    }
+                let job = self.spawn_job(
+                    self.get_compiling_command(for_build)
+                        .arg("-fsyntax-only")
+                        .arg(&for_build.path),
+                );
+                let result = match job {
+                    Ok(job) => job.finish(),
+                    Err(err) => JobResult::from_spawn_error(err),
+                };
+                summary.add(&Diagnostic::collect(&result.output));
+
+                if result.success {
+                    self.print_job_result("Ok".green().bold(), &format!("'{}'", for_build.path), &result);
+                    compiled_number += 1;
+                } else {
+                    self.print_job_result("Fail".red().bold(), &format!("'{}'", for_build.path), &result);
+                    is_successful = false;
                 }
 
                 built.push(for_build.path.clone());
             }
         }
+        self.print_diagnostics_summary(&summary);
         if !is_successful {
             println!(
                 "{:>RESULT_BORDER_WIDTH$} Ok {}/{}",
@@ -318,250 +930,550 @@ impl Section {
             );
             return false;
         }
-        println!(
-            "{:>RESULT_BORDER_WIDTH$}",
-            "Everything is ok".green().bold()
-        );
-        return true;
-    }
-
-    pub fn link(&self) -> bool {
-        let objects: Vec<String> =
-            // todo mb just collect files from prev state?
-            std::fs::read_dir(self.get_binary_path())
-                .unwrap()
-                .filter(|file| {
-                    let file = file.as_ref().unwrap();
-                    return file.file_name().to_str().unwrap().ends_with(".o");
-                })
-                .map(|file| {
-                    let file = file.unwrap();
-                    return file
-                        .path()
-                        .canonicalize()
-                        .unwrap()
-                        .to_str()
-                        .unwrap()
-                        .to_string();
-                })
-                .collect();
+        if !self.options.quiet {
+            println!(
+                "{:>RESULT_BORDER_WIDTH$}",
+                "Everything is ok".green().bold()
+            );
+        }
+        true
+    }
+
+    fn finish_linking(&self, job: std::io::Result<Job>, complete: &str, timings: &mut Timings) -> bool {
+        let result = match job {
+            Ok(job) => job.finish(),
+            Err(err) => JobResult::from_spawn_error(err),
+        };
+        timings.record_linking(&self.get_outlet_path(), result.duration);
+        if result.success {
+            self.print_job_result(complete.green().bold(), &"linking".cyan().to_string(), &result);
+        } else {
+            self.print_job_result("Fail".red().bold(), &"linking".cyan().to_string(), &result);
+        }
+        result.success
+    }
+
+    pub fn link(&self, timings: &mut Timings) -> bool {
+        let objects = self.get_object_paths();
         // linking
-        if (self.outlet_type == "executable") {
-            let mut child = std::process::Command::new(&self.profile.compiler)
-                .args(&self.profile.linking_directories)
+        if self.outlet_type == "executable" {
+            // Libraries go after objects, otherwise linker drops symbols
+            // which aren't required yet at the moment library is scanned
+            let mut command = self.toolchain.get_command(&self.toolchain.compiler);
+            command
+                .args(self.toolchain.get_linking_flags())
                 .args(&self.profile.linking_options)
+                .args(&self.flags.linking_options)
                 .args(objects)
+                .args(self.collect_piped_outlets())
+                .args(self.get_library_arguments())
+                .args(self.get_package_linking_flags())
                 .arg("-o")
-                .arg(format!(
-                    ".abs/{}/{}/{}",
-                    self.name, self.profile.name, self.name
-                ))
-                .spawn()
-                .unwrap();
-
-            match child.wait() {
-                Ok(exit_status) => {
-                    if exit_status.success() {
-                        println!(
-                            "{:>RESULT_BORDER_WIDTH$} {}",
-                            "Complete executable".green().bold(),
-                            "linking".cyan()
-                        );
-                    } else {
-                        println!(
-                            "{:>RESULT_BORDER_WIDTH$} {}",
-                            "Fail".red().bold(),
-                            "linking".cyan()
-                        );
-                        return false;
-                    }
-                }
-                Err(_) => {
-                    println!(
-                        "{:>RESULT_BORDER_WIDTH$} {}",
-                        "Fail".red().bold(),
-                        "linking".cyan()
-                    );
-                    return false;
-                }
+                .arg(self.get_outlet_path());
+            if self.options.dry_run {
+                println!("{}", job::to_command_line(&command));
+                return true;
             }
-        } else if (self.outlet_type == "library") {
-            let mut child = std::process::Command::new("ar")
-                .arg("rcs")
-                .arg("-o")
-                .arg(format!(
-                    ".abs/{}/{}/lib{}.a",
-                    self.name, self.profile.name, self.name
-                ))
-                .args(objects)
-                .spawn()
-                .unwrap();
-
-            match child.wait() {
-                Ok(exit_status) => {
-                    if exit_status.success() {
-                        println!(
-                            "{:>RESULT_BORDER_WIDTH$} {}",
-                            "Complete static library".green().bold(),
-                            "linking".cyan()
-                        );
-                    } else {
-                        println!(
-                            "{:>RESULT_BORDER_WIDTH$} {}",
-                            "Fail".red().bold(),
-                            "linking".cyan()
-                        );
-                        return false;
+            return self.finish_linking(self.spawn_job(&mut command), "Complete executable", timings);
+        } else if self.outlet_type == "library" {
+            // Archive is created from scratch next to the old one and replaces it
+            // only on success, so a failed archiver doesn't leave a broken library
+            let outlet_path = self.get_outlet_path();
+            let temporary_path = format!("{outlet_path}.tmp");
+            let mut command = std::process::Command::new(&self.toolchain.archiver);
+            command
+                .arg(if self.profile.thin_archive { "rcsT" } else { "rcs" })
+                .arg(&temporary_path)
+                .args(&objects);
+            if self.options.dry_run {
+                println!("{}", job::to_command_line(&command));
+                return true;
+            }
+            std::fs::remove_file(&temporary_path);
+            // Thin archives refer to objects by path, so they aren't cached
+            let key = match (&self.cache, self.profile.thin_archive) {
+                (Some(_), false) => self.get_archive_cache_key(&objects),
+                _ => None,
+            };
+            if let (Some(cache), Some(key)) = (&self.cache, &key) {
+                if cache.restore(key, "a", &outlet_path) {
+                    if !self.options.quiet {
+                        println!("{:>RESULT_BORDER_WIDTH$} static library", "Cached".green().bold());
                     }
+                    return true;
                 }
-                Err(_) => {
-                    println!(
-                        "{:>RESULT_BORDER_WIDTH$} {}",
-                        "Fail".red().bold(),
-                        "linking".cyan()
-                    );
-                    return false;
+            }
+            if !self.finish_linking(self.spawn_job(&mut command), "Complete static library", timings) {
+                std::fs::remove_file(&temporary_path);
+                return false;
+            }
+            if let (Some(cache), Some(key)) = (&self.cache, &key) {
+                cache.store(key, "a", &temporary_path);
+            }
+            if let Err(err) = std::fs::rename(&temporary_path, &outlet_path) {
+                println!(
+                    "{:>RESULT_BORDER_WIDTH$} to replace '{}': {}",
+                    "Failed".red().bold(),
+                    outlet_path,
+                    err
+                );
+                return false;
+            }
+        }
+        true
+    }
+
+    fn get_package_compiling_flags(&self) -> Vec<String> {
+        self.packages
+            .iter()
+            .flat_map(|package| package.compiling_flags.iter().cloned())
+            .collect()
+    }
+
+    /// Libraries of own packages and of packages used by piped sections,
+    /// which are needed as well when their static libraries are linked
+    fn get_package_linking_flags(&self) -> Vec<String> {
+        let mut flags: Vec<String> = self
+            .packages
+            .iter()
+            .flat_map(|package| package.linking_flags.iter().cloned())
+            .collect();
+        for pipe in &self.pipes {
+            if let Some(pipe) = pipe.upgrade() {
+                flags.append(&mut pipe.borrow().get_package_linking_flags());
+            }
+        }
+        let mut unique: Vec<String> = vec![];
+        for flag in flags.into_iter().rev() {
+            if !unique.contains(&flag) {
+                unique.push(flag);
+            }
+        }
+        unique.reverse();
+        unique
+    }
+
+    fn get_library_arguments(&self) -> Vec<String> {
+        let directories = self
+            .library_directories
+            .iter()
+            .map(|directory| format!("-L{directory}"));
+        let libraries = self.profile.libraries.iter().map(|library| {
+            if library.starts_with('-') || library.contains('/') {
+                library.clone()
+            } else {
+                format!("-l{library}")
+            }
+        });
+        directories.chain(libraries).collect()
+    }
+
+    /// Libraries of piped sections ordered so that every library goes
+    /// before the libraries it depends on
+    pub fn collect_piped_outlets(&self) -> Vec<String> {
+        let mut outlets: Vec<String> = vec![];
+        for pipe in &self.pipes {
+            if let Some(pipe) = pipe.upgrade() {
+                let pipe = pipe.borrow();
+                if pipe.outlet_type == "library" || pipe.outlet_type == "shared" {
+                    outlets.push(pipe.get_outlet_path());
                 }
+                outlets.append(&mut pipe.collect_piped_outlets());
+            }
+        }
+        let mut unique: Vec<String> = vec![];
+        for outlet in outlets.into_iter().rev() {
+            if !unique.contains(&outlet) {
+                unique.push(outlet);
+            }
+        }
+        unique.reverse();
+        unique
+    }
+
+    /// Object which contains the compiled source: either its own or the one of its unity batch
+    fn get_unit_object_path(&self, file: &File) -> Option<String> {
+        let artifacts_path = self.get_artifacts_path();
+        let batch = self
+            .unity
+            .as_ref()
+            .and_then(|unity| unity.find_batch(file).map(|index| unity.get_object_path(index, &artifacts_path)));
+        match batch {
+            Some(object_path) => Some(object_path),
+            None => file.get_object_path_in(&artifacts_path).ok(),
+        }
+    }
+
+    fn get_archive_cache_key(&self, objects: &[String]) -> Option<String> {
+        let mut key = CacheKey::new("archive", &self.root);
+        key.add_text(&self.toolchain.archiver);
+        // Names of objects depend on paths of sources, so only their content is hashed
+        for object in objects {
+            if !key.add_content(object) {
+                return None;
             }
         }
-        return true;
+        Some(key.finish())
+    }
+
+    /// Objects of the current sources. Objects left from removed sources
+    /// or from builds in another mode aren't linked.
+    fn get_object_paths(&self) -> Vec<String> {
+        let mut objects: Vec<String> = vec![];
+        for object_path in self.files.iter().filter_map(|file| self.get_unit_object_path(file)) {
+            if !objects.contains(&object_path) {
+                objects.push(object_path);
+            }
+        }
+        objects
     }
 
     pub fn collect_missing_objects(&self) -> Vec<File> {
         self.files
             .iter()
             .filter(|file| {
-                if let Ok(name) = &file.get_object_path_in(&format!("{}/{}", &self.name, &self.profile.name)) {
-                    if !Path::new(&name).exists() {
-                        return true;
-                    }
-                } 
-                return false;
+                self.get_unit_object_path(file)
+                    .map(|object_path| !Path::new(&object_path).exists())
+                    .unwrap_or(false)
             })
-            .map(|file| file.clone())
+            .cloned()
             .collect()
     }
 
-    pub fn build(&self) -> bool {
-        std::fs::create_dir_all(self.get_binary_path());
+    /// Groups sources which have to be compiled into units which are passed to the compiler
+    fn group_into_units<'a>(&'a self, sources: &[&'a File]) -> Vec<CompilationUnit<'a>> {
+        let artifacts_path = self.get_artifacts_path();
+        let mut units: Vec<CompilationUnit> = vec![];
+        for source in sources {
+            let batch = self
+                .unity
+                .as_ref()
+                .and_then(|unity| unity.find_batch(source).map(|index| (unity, index)));
+            match batch {
+                Some((unity, index)) => {
+                    let input = unity.get_batch_path(index);
+                    if units.iter().all(|unit| unit.input != input) {
+                        units.push(CompilationUnit {
+                            input,
+                            object: unity.get_object_path(index, &artifacts_path),
+                            sources: unity.get_batch(index).iter().collect(),
+                        });
+                    }
+                }
+                None => units.push(CompilationUnit {
+                    input: source.path.clone(),
+                    object: source.get_object_path_in(&artifacts_path).unwrap(),
+                    sources: vec![source],
+                }),
+            }
+        }
+        units
+    }
 
-        let mut modified = self.get_modified(&self.sources_of_dependency.keys().cloned().collect());
+    pub fn build(&self) -> bool {
+        let mut timings = Timings::new();
+        let is_successful = self.compile_and_link(&mut timings);
+        if timings.is_empty() {
+            return is_successful;
+        }
+        if !self.options.quiet {
+            timings.print_summary();
+        }
+        if self.options.timings {
+            let report_path = format!("{}/timings.json", self.get_artifacts_path());
+            if let Err(err) = std::fs::write(&report_path, timings.to_json(&self.name, &self.profile.name)) {
+                println!(
+                    "{:>RESULT_BORDER_WIDTH$} to write '{}': {}",
+                    "Failed".red().bold(),
+                    report_path,
+                    err
+                );
+            }
+        }
+        is_successful
+    }
 
-        modified.append(&mut self.collect_missing_objects());
+    /// Command of the unit is stored next to its object, so the unit
+    /// is compiled again when flags change
+    fn get_command_path(object: &str) -> String {
+        format!("{object}.command")
+    }
 
-        if modified.is_empty() && self.check_is_outlet_exist() {
-            println!(
-                "{:>RESULT_BORDER_WIDTH$} {}",
-                "Compiling".bright_green(),
-                "nothing to compile"
-            );
-            return true;
-        }
+    /// Objects built before commands were stored are considered up to date
+    fn is_command_changed(object: &str, command: &std::process::Command) -> bool {
+        std::fs::read_to_string(Section::get_command_path(object))
+            .map(|stored| stored != job::to_command_line(command))
+            .unwrap_or(false)
+    }
 
-        let mut built: Vec<&File> = vec![];
+    fn store_command(object: &str, command: &std::process::Command) {
+        std::fs::write(Section::get_command_path(object), job::to_command_line(command));
+    }
 
-        let mut failed: Vec<File> = vec![];
+    /// Compiler command for the unit without the output
+    fn get_unit_command(&self, unit: &CompilationUnit) -> std::process::Command {
+        let mut command = self.get_compiling_command(unit.sources[0]);
+        // Cache keys don't depend on the place of the checkout, so
+        // neither may debug information and `__FILE__` of cached objects
+        if self.cache.is_some() && !self.root.is_empty() {
+            command
+                .arg(format!("-fdebug-prefix-map={}=.", self.root))
+                .arg(format!("-ffile-prefix-map={}=.", self.root));
+        }
+        command.arg("-c").arg(&unit.input);
+        command
+    }
 
-        let mut children: HashMap<&File, Child> = HashMap::new();
+    /// Files which make translation units dirty with the reason for each of them.
+    /// The precompiled header is rebuilt here, it's needed by every translation unit.
+    fn collect_dirty_files(&self, timings: &mut Timings) -> Option<Vec<(File, String)>> {
+        let artifacts_path = self.get_artifacts_path();
+        let mut dirty: Vec<(File, String)> = self
+            .get_modified(&self.get_tracked_files())
+            .into_iter()
+            .map(|file| {
+                let reason = if file.get_object_path_in(&artifacts_path).is_ok() {
+                    "modified source".to_string()
+                } else if self.is_system_file(&file) {
+                    "modified system headers".to_string()
+                } else {
+                    format!("modified header '{}'", self.get_display_path(&file))
+                };
+                (file, reason)
+            })
+            .collect();
+        dirty.extend(
+            self.collect_missing_objects()
+                .into_iter()
+                .map(|file| (file, NOT_BUILT_REASON.to_string())),
+        );
 
-        let mut handle_child = |child: &mut Child, file: &File| -> bool {
-            let file = file.clone();
-            match child.try_wait() {
-                Ok(result) => {
-                    if result.is_none() {
-                        return true;
-                    }
-                    let result = result.unwrap();
-                    if result.success() {
-                        println!(
-                            "{:>RESULT_BORDER_WIDTH$} '{}'",
-                            "Complete".green().bold(),
-                            file.path
-                        );
-                        self.freeze(&file);
-                    } else {
-                        println!(
-                            "{:>RESULT_BORDER_WIDTH$} '{}'",
-                            "Fail".red().bold(),
-                            file.path
-                        );
-                        failed.push(file);
+        // Batches which got another set of sources are compiled even if the sources didn't change
+        if let Some(unity) = &self.unity {
+            let changed = if self.options.dry_run {
+                Ok(unity.get_changed())
+            } else {
+                unity.write()
+            };
+            match changed {
+                Ok(changed) => {
+                    for index in changed {
+                        let reason = format!("sources of '{}' changed", unity.get_batch_path(index));
+                        dirty.extend(unity.get_batch(index).iter().map(|file| (file.clone(), reason.clone())));
                     }
-                    return false;
                 }
-                Err(_) => return true,
+                Err(err) => {
+                    println!(
+                        "{:>RESULT_BORDER_WIDTH$} to write unity files: {}",
+                        "Failed".red().bold(),
+                        err
+                    );
+                    return None;
+                }
+            }
+        }
+
+        let translation_units: Vec<&File> = self
+            .files
+            .iter()
+            .filter(|file| file.get_object_path_in(&artifacts_path).is_ok())
+            .collect();
+        if let Some(header) = &self.precompiled_header {
+            let modified: Vec<File> = dirty.iter().map(|(file, _)| file.clone()).collect();
+            if self.is_precompiled_header_outdated(header, &modified) {
+                if !self.precompile_header(header, timings) {
+                    return None;
+                }
+                // Every translation unit is compiled with the precompiled header
+                let reason = format!("precompiled header '{}' changed", self.get_display_path(header));
+                dirty.extend(translation_units.iter().map(|file| ((*file).clone(), reason.clone())));
             }
+        }
+
+        for unit in self.group_into_units(&translation_units) {
+            if Section::is_command_changed(&unit.object, &self.get_unit_command(&unit)) {
+                dirty.extend(unit.sources.iter().map(|file| ((*file).clone(), "flags changed".to_string())));
+            }
+        }
+        Some(dirty)
+    }
+
+    fn compile_and_link(&self, timings: &mut Timings) -> bool {
+        if !self.options.dry_run {
+            std::fs::create_dir_all(self.get_binary_path());
+        }
+
+        let dirty = match self.collect_dirty_files(timings) {
+            Some(dirty) => dirty,
+            None => return false,
         };
 
-        for modified_file in &modified {
-            let mut is_all_compiled = true;
-            self.sources_of_dependency[modified_file].iter().for_each(|for_build| {
+        if dirty.is_empty() && self.check_is_outlet_exist() {
+            if !self.options.quiet {
+                println!(
+                    "{:>RESULT_BORDER_WIDTH$} nothing to compile",
+                    "Compiling".bright_green()
+                );
+            }
+            return true;
+        }
 
-                let object_path = for_build.get_object_path_in(&format!("{}/{}", &self.name, &self.profile.name));
-                if built.contains(&for_build)
-                    || for_build.path.ends_with(".hpp")
-                    || for_build.path.ends_with(".h") 
-                    || object_path.is_err() {
-                    return;
+        let mut built: Vec<&File> = vec![];
+        let mut reasons: HashMap<&File, Vec<&str>> = HashMap::new();
+        for (modified_file, reason) in &dirty {
+            for for_build in self.get_dependent_files(modified_file) {
+                if for_build.path.ends_with(".hpp")
+                    || for_build.path.ends_with(".h")
+                    || for_build.get_object_path_in(&self.get_artifacts_path()).is_err() {
+                    continue;
                 }
-                let object_path = object_path.unwrap();
-                if for_build.path != modified_file.path
-                    && for_build.is_modified_in(&format!("{}/{}", &self.name, &self.profile.name)) {
-                    return;
+                let reasons = reasons.entry(for_build).or_default();
+                if !reasons.contains(&reason.as_str()) {
+                    reasons.push(reason);
                 }
-                let included_directories_argument = self
-                    .include_directories
-                    .iter()
-                    .map(|str| format!("-I{}", str));
-
-                while children.len() >= 8 {
-                    children.retain(|file, child| handle_child(child, *file));
+                if !built.contains(&for_build) {
+                    built.push(for_build);
+                }
+            }
+        }
+        if self.options.explain {
+            // Other reasons of a unit which was never built only repeat that
+            for reasons in reasons.values_mut() {
+                if reasons.contains(&NOT_BUILT_REASON) {
+                    *reasons = vec![NOT_BUILT_REASON];
                 }
-                children.insert(
-                    &for_build,
-                    std::process::Command::new(&self.profile.compiler)
-                        .arg("-c")
-                        .arg(&for_build.path)
-                        .args(&self.profile.options)
-                        .arg(&self.profile.standard)
-                        .args(&self.profile.defines)
-                        .args(included_directories_argument)
-                        .arg("-o")
-                        .arg(object_path)
-                        .spawn()
-                        .unwrap(),
+            }
+            for source in &built {
+                println!(
+                    "{:>RESULT_BORDER_WIDTH$} '{}': {}",
+                    "Dirty".yellow().bold(),
+                    self.get_display_path(source),
+                    reasons[source].join(", ")
                 );
+            }
+            if built.is_empty() {
+                println!(
+                    "{:>RESULT_BORDER_WIDTH$} '{}': missing outlet",
+                    "Dirty".yellow().bold(),
+                    self.shorten_path(&self.get_outlet_path())
+                );
+            }
+        }
+        let units = self.group_into_units(&built);
+        let compiled_number: usize = units.iter().map(|unit| unit.sources.len()).sum();
 
-                built.push(&for_build);
-            });
+        if self.options.dry_run {
+            for unit in &units {
+                let mut command = self.get_unit_command(unit);
+                println!("{}", job::to_command_line(command.arg("-o").arg(&unit.object)));
+            }
+            return self.link(timings);
         }
-        while children.len() > 0 {
-            children.retain(|file, child| handle_child(child, *file));
+
+        let mut failed: Vec<File> = vec![];
+
+        let mut summary = DiagnosticsSummary::default();
+
+        let mut children: Vec<(&CompilationUnit, Option<String>, Job)> = vec![];
+
+        let mut progress = Progress::new(units.len(), self.options.progress);
+
+        let mut cached_number = 0;
+
+        // On terminals only results with output are printed, the progress line shows the rest
+        let mut handle_result = |result: JobResult, unit: &CompilationUnit, key: &Option<String>, progress: &mut Progress| {
+            summary.add(&Diagnostic::collect(&result.output));
+            timings.record_compiling(&self.shorten_path(&unit.input), result.duration);
+            let is_printed = !progress.is_enabled() || !result.success || !result.output.is_empty();
+            if is_printed {
+                progress.clear();
+            }
+            if result.success {
+                if is_printed {
+                    self.print_job_result("Complete".green().bold(), &format!("'{}'", self.shorten_path(&unit.input)), &result);
+                }
+                unit.sources.iter().for_each(|source| self.freeze(source));
+                if let (Some(cache), Some(key)) = (&self.cache, key) {
+                    cache.store(key, "o", &unit.object);
+                }
+            } else {
+                self.print_job_result("Fail".red().bold(), &format!("'{}'", self.shorten_path(&unit.input)), &result);
+                failed.extend(unit.sources.iter().map(|source| (*source).clone()));
+            }
+            if is_printed {
+                progress.redraw();
+            }
+        };
+
+        for unit in &units {
+            let mut command = self.get_unit_command(unit);
+            let key = self.get_object_cache_key(unit, &command);
+            if let (Some(cache), Some(key)) = (&self.cache, &key) {
+                if cache.restore(key, "o", &unit.object) {
+                    cached_number += 1;
+                    progress.start(&self.shorten_path(&unit.input));
+                    if !self.options.quiet && !progress.is_enabled() {
+                        println!("{:>RESULT_BORDER_WIDTH$} '{}'", "Cached".green().bold(), self.shorten_path(&unit.input));
+                    }
+                    unit.sources.iter().for_each(|source| self.freeze(source));
+                    Section::store_command(&unit.object, &command);
+                    continue;
+                }
+            }
+            // Object may be a hard link to an entry of the cache which must not be overwritten
+            std::fs::remove_file(&unit.object);
+            // Object of a failed unit is missing, so it's compiled again regardless of the command
+            Section::store_command(&unit.object, &command);
+            while children.len() >= self.options.get_jobs() {
+                Section::poll_jobs(&mut children, &mut progress, &mut handle_result);
+            }
+            progress.start(&self.shorten_path(&unit.input));
+            // Missing compiler or launcher fails the unit like a compiler error does
+            match self.spawn_job(command.arg("-o").arg(&unit.object)) {
+                Ok(job) => children.push((unit, key, job)),
+                Err(err) => handle_result(JobResult::from_spawn_error(err), unit, &key, &mut progress),
+            }
+        }
+        while !children.is_empty() {
+            Section::poll_jobs(&mut children, &mut progress, &mut handle_result);
         }
+        progress.clear();
+        let translation_units = self
+            .files
+            .iter()
+            .filter(|file| file.get_object_path_in(&self.get_artifacts_path()).is_ok())
+            .count();
+        timings.set_cached(cached_number);
+        timings.set_skipped(translation_units.saturating_sub(compiled_number));
 
-        for (dep, srcs) in &self.sources_of_dependency {
-            if srcs.iter().all(|src| !failed.contains(&src)) {
+        // A header is frozen only when every unit reaching it was compiled
+        for dep in self.sources_of_dependency.keys() {
+            if self.get_dependent_files(dep).iter().all(|src| !failed.contains(src)) {
                 self.freeze(dep);
             }
         }
 
+        self.print_diagnostics_summary(&summary);
+
         if !failed.is_empty() {
             println!(
                 "{:>RESULT_BORDER_WIDTH$} {}. Compiled {}/{}",
                 "Fail".red().bold(),
                 "compiling".cyan(),
-                built.len() - failed.len(),
-                built.len()
+                compiled_number - failed.len(),
+                compiled_number
             );
             return false;
         }
-        println!(
-            "{:>RESULT_BORDER_WIDTH$} {}",
-            "Complete".green().bold(),
-            "compiling".cyan()
-        );
+        if !self.options.quiet {
+            println!(
+                "{:>RESULT_BORDER_WIDTH$} {}",
+                "Complete".green().bold(),
+                "compiling".cyan()
+            );
+        }
 
-        return self.link();
+        self.link(timings)
     }
 
     pub fn run(&self) -> bool {
@@ -575,18 +1487,90 @@ impl Section {
         if !self.build() {
             return false;
         }
-        println!(
-            "{:>RESULT_BORDER_WIDTH$} '{}' with profile '{}'",
-            "Running".bright_green(),
-            self.name,
-            self.profile.name
-        );
-        let mut child = std::process::Command::new(format!(
-            ".abs/{}/{}/{}",
-            self.name, self.profile.name, self.name
-        ))
-        .spawn()
+        if self.options.dry_run {
+            return true;
+        }
+        // abs returns once the program is started, it isn't waited for
+        self.spawn().is_some()
+    }
+
+    /// Starts the built executable without waiting for it
+    pub fn spawn(&self) -> Option<std::process::Child> {
+        if !self.options.quiet {
+            println!(
+                "{:>RESULT_BORDER_WIDTH$} '{}' with profile '{}'",
+                "Running".bright_green(),
+                self.name,
+                self.profile.name
+            );
+        }
+        let mut command = std::process::Command::new(self.get_outlet_path());
+        self.print_command(&command);
+        command.spawn().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tank with `main.cpp` which includes `b.hpp`, `b.hpp` includes `a.hpp` of the same include directory
+    fn create_tank(name: &str) -> std::path::PathBuf {
+        let directory = std::env::temp_dir().join(format!("abs-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(directory.join("source")).unwrap();
+        std::fs::create_dir_all(directory.join("include")).unwrap();
+        std::fs::write(
+            directory.join("abs.toml"),
+            "[tank]\nname = \"nested\"\nversion = \"0.1.0\"\n\n\
+             [toolchain]\ncache = true\n\n\
+             [sections.main]\nsource = \"source\"\ninclude = \"include\"\n",
+        )
         .unwrap();
-        return true;
+        std::fs::write(directory.join("source/main.cpp"), "#include \"b.hpp\"\nint main() { return VALUE; }\n").unwrap();
+        std::fs::write(directory.join("include/b.hpp"), "#include \"a.hpp\"\n").unwrap();
+        std::fs::write(directory.join("include/a.hpp"), "#define VALUE 1\n").unwrap();
+        directory.canonicalize().unwrap()
+    }
+
+    fn load_section(directory: &Path) -> (Tank, Rc<RefCell<Section>>) {
+        let options = Options {
+            quiet: true,
+            ..Default::default()
+        };
+        let tank = Tank::new(&directory.join("abs.toml").to_string_lossy(), "debug", options).unwrap();
+        let section = tank.get_sections()[0].upgrade().unwrap();
+        (tank, section)
+    }
+
+    fn get_cache_key(section: &Section) -> Option<String> {
+        let units = section.group_into_units(&section.files.iter().collect::<Vec<&File>>());
+        section.get_object_cache_key(&units[0], &section.get_unit_command(&units[0]))
+    }
+
+    #[test]
+    fn headers_included_by_headers() {
+        let directory = create_tank("nested-headers");
+        let (_tank, section) = load_section(&directory);
+        let section = section.borrow();
+        let edges = section.get_include_edges();
+        assert!(edges.contains(&("include/b.hpp".to_string(), "include/a.hpp".to_string())));
+        assert!(edges.contains(&("source/main.cpp".to_string(), "include/b.hpp".to_string())));
+        assert_eq!(
+            section.get_rebuilt_units(&directory.join("include/a.hpp")),
+            Some(vec!["source/main.cpp".to_string()])
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn cache_key_covers_headers_included_by_headers() {
+        let directory = create_tank("nested-cache-key");
+        let (_tank, section) = load_section(&directory);
+        let key = get_cache_key(&section.borrow()).unwrap();
+        std::fs::write(directory.join("include/a.hpp"), "#define VALUE 2\n").unwrap();
+        let (_tank, section) = load_section(&directory);
+        assert_ne!(get_cache_key(&section.borrow()).unwrap(), key);
+        std::fs::remove_dir_all(&directory).unwrap();
     }
 }
@@ -1,10 +1,23 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::rc::{Rc, Weak};
 
+use super::git;
+use super::graph::Graph;
+use super::lockfile::Lockfile;
+use super::options::Options;
+use super::package::Package;
+use super::profile::Profile;
 use super::profiles_manager::ProfilesManager;
-use super::section::Section;
+use super::section::{Section, RESULT_BORDER_WIDTH};
+use colored::Colorize;
+use super::target::Target;
+use super::toolchain::Toolchain;
+use super::workspace::Workspace;
 
+#[allow(unused)]
 #[derive(Debug)]
 pub enum TankError {
     ConfigFileDoesntExist(String),
@@ -12,6 +25,8 @@ pub enum TankError {
     MandatoryLack(String),
     WrongTypeOfField(String),
     SectionError(String),
+    ToolchainError(String),
+    DependencyError(String),
 }
 
 #[derive(Debug)]
@@ -21,25 +36,84 @@ pub struct Tank {
     config: toml::Value,
     version: String, // todo Probably semver type?
     sections: Vec<Rc<RefCell<Section>>>,
+    options: Rc<Options>,
+    toolchain: Rc<Toolchain>,
+    packages: HashMap<String, Rc<Package>>,
+    /// Members of the workspace which are dependencies are shared with `members`
+    dependencies: BTreeMap<String, Rc<Tank>>,
+    /// Tanks of `[workspace] members` in the order they are listed
+    members: Vec<Rc<Tank>>,
+    /// Directory of the configuration file, other paths are relative to it
+    root: String,
+    build_directory: String,
+    /// Configuration files of this tank and tanks which depend on it
+    ancestors: Vec<PathBuf>,
+    /// Shared by the main tank and all its dependencies
+    lockfile: Rc<RefCell<Lockfile>>,
+    workspace: Rc<Workspace>,
+    /// Set once library sections are built, so a tank shared by several dependents is built once
+    libraries_built: Cell<bool>,
 
     profiles_manager: ProfilesManager,
 }
 
 #[allow(unused)]
 impl Tank {
-    pub fn new(config_name: &str, profile_name: &str) -> Result<Tank, TankError> {
+    /// Loads the tank from the configuration file. When the tank is a member of
+    /// a workspace the whole workspace is loaded so the member shares its toolchain
+    /// and build directory.
+    pub fn new(config_name: &str, profile_name: &str, options: Options) -> Result<Tank, TankError> {
+        let manifest = Path::new(config_name)
+            .canonicalize()
+            .map_err(|err| TankError::ConfigFileDoesntExist(err.to_string()))?;
+        let workspace_config = match Workspace::find_root(&manifest) {
+            Some(workspace_config) => workspace_config,
+            None => return Tank::load(config_name, profile_name, Rc::new(options), None),
+        };
+        let mut workspace = Tank::load(
+            &workspace_config.to_string_lossy(),
+            profile_name,
+            Rc::new(options),
+            None,
+        )?;
+        // Other members may depend on the member, they are dropped before it's taken out
+        let members = std::mem::take(&mut workspace.members);
+        drop(workspace);
+        let member = members
+            .into_iter()
+            .find(|member| member.ancestors.last() == Some(&manifest))
+            .expect("Member of the workspace is loaded");
+        Ok(Rc::try_unwrap(member).expect("Member of the workspace isn't shared"))
+    }
+
+    /// Loads a tank either as the main one or as a dependency `name` of `parent`.
+    /// Dependencies share toolchain of the parent and place their artifacts
+    /// into `deps/<name>` of the parent's build directory.
+    fn load(
+        config_name: &str,
+        profile_name: &str,
+        options: Rc<Options>,
+        parent: Option<(&Tank, &str)>,
+    ) -> Result<Tank, TankError> {
         let mut config: toml::Value = toml::from_str(
             &fs::read_to_string(config_name)
                 .map_err(|err| TankError::ConfigFileDoesntExist(err.to_string()))?,
         )
         .map_err(|err| TankError::WrongFormatOfToml(err.to_string()))?;
 
-        let tank_config = config
-            .get("tank")
-            .ok_or_else(|| {
-                TankError::MandatoryLack("Can't find 'tank' table which is mandatory".to_string())
-            })?
-            .clone();
+        // Root of a workspace may have no sections and therefore no 'tank' table
+        let tank_config = match (config.get("tank"), config.get("workspace")) {
+            (Some(tank_config), _) => tank_config.clone(),
+            (None, Some(_)) => toml::toml! {
+                name = "workspace"
+                version = "0.0.0"
+            },
+            (None, None) => {
+                return Err(TankError::MandatoryLack(
+                    "Can't find 'tank' table which is mandatory".to_string(),
+                ))
+            }
+        };
         let name_of_tank = tank_config
             .get("name")
             .ok_or_else(|| TankError::MandatoryLack("Can't find name of tank".to_string()))?;
@@ -47,6 +121,55 @@ impl Tank {
             .get("version")
             .ok_or_else(|| TankError::MandatoryLack("Can't find version of tank".to_string()))?;
 
+        let manifest = Path::new(config_name)
+            .canonicalize()
+            .map_err(|err| TankError::ConfigFileDoesntExist(err.to_string()))?;
+        // Paths derived from the root don't depend on the directory abs was started from,
+        // so the same tank always gets the same commands and artifacts
+        let root = manifest
+            .parent()
+            .map(|parent| parent.to_string_lossy().to_string())
+            .unwrap_or_else(|| "/".to_string());
+        let mut ancestors = vec![];
+        let build_directory = match parent {
+            Some((parent, name)) => {
+                if parent.ancestors.contains(&manifest) {
+                    return Err(TankError::DependencyError(format!(
+                        "'{name}' forms a cycle of dependencies"
+                    )));
+                }
+                ancestors = parent.ancestors.clone();
+                parent
+                    .workspace
+                    .get_member_directory(&manifest)
+                    .unwrap_or_else(|| format!("{}/deps/{}", parent.build_directory, name))
+            }
+            None => Tank::get_root_build_directory(&config, &root, &options)?,
+        };
+        ancestors.push(manifest);
+
+        let workspace = match parent {
+            Some((parent, _)) => Rc::clone(&parent.workspace),
+            None => Rc::new(
+                Workspace::from_config(&config, &root, &build_directory)
+                    .map_err(TankError::WrongTypeOfField)?,
+            ),
+        };
+
+        let profiles_manager = ProfilesManager::new(config.get("profiles"));
+        let profile = profiles_manager.get(profile_name).expect("Exist profile name"); // todo
+
+        let toolchain = match parent {
+            Some((parent, _)) => parent.get_toolchain(),
+            None => Rc::new(Tank::detect_toolchain(&config, &root, &options, &profile, &build_directory)?),
+        };
+        let lockfile = match parent {
+            Some((parent, _)) => Rc::clone(&parent.lockfile),
+            None => Rc::new(RefCell::new(
+                Lockfile::load(&format!("{root}/abs.lock")).map_err(TankError::DependencyError)?,
+            )),
+        };
+
         let mut tank = Tank {
             name: name_of_tank
                 .as_str()
@@ -60,54 +183,437 @@ impl Tank {
                 })?
                 .to_string(),
             sections: vec![],
-            profiles_manager: ProfilesManager::new(config.get("profiles")),
+            options: Rc::clone(&options),
+            toolchain,
+            packages: HashMap::new(),
+            dependencies: BTreeMap::new(),
+            members: vec![],
+            root,
+            build_directory,
+            ancestors,
+            lockfile,
+            workspace,
+            libraries_built: Cell::new(false),
+            profiles_manager,
         };
 
-        let profile = tank.profiles_manager.get(profile_name).expect("Exist profile name"); // todo
+        if let Some(toml::Value::Table(dependencies)) = config.get("dependencies") {
+            for (name, value) in dependencies {
+                if let Some(requirement) = value.get("pkg-config") {
+                    let requirement = requirement.as_str().ok_or_else(|| {
+                        TankError::WrongTypeOfField(format!("pkg-config of '{name}' is string type"))
+                    })?;
+                    let package = Package::from_pkg_config(name, requirement).map_err(TankError::DependencyError)?;
+                    tank.packages.insert(name.to_string(), Rc::new(package));
+                } else if let Some(path) = value.get("path") {
+                    let path = path.as_str().ok_or_else(|| {
+                        TankError::WrongTypeOfField(format!("path of '{name}' is string type"))
+                    })?;
+                    let directory = tank.resolve_path(path);
+                    let dependency = tank.load_dependency(name, &directory, profile_name)?;
+                    tank.dependencies.insert(name.to_string(), dependency);
+                } else if let Some(url) = value.get("git") {
+                    let url = url.as_str().ok_or_else(|| {
+                        TankError::WrongTypeOfField(format!("git of '{name}' is string type"))
+                    })?;
+                    let rev = match value.get("rev") {
+                        Some(rev) => rev.as_str().ok_or_else(|| {
+                            TankError::WrongTypeOfField(format!("rev of '{name}' is string type"))
+                        })?,
+                        None => "HEAD",
+                    };
+                    let directory = git::fetch(name, url, rev, &mut tank.lockfile.borrow_mut())
+                        .map_err(|err| TankError::DependencyError(format!("'{name}': {err}")))?;
+                    let dependency = tank.load_dependency(name, &directory, profile_name)?;
+                    tank.dependencies.insert(name.to_string(), dependency);
+                } else {
+                    return Err(TankError::DependencyError(format!(
+                        "Can't find source of dependency '{name}'"
+                    )));
+                }
+            }
+        }
+
+        if parent.is_none() {
+            for member in tank.workspace.get_members() {
+                let directory = tank.resolve_path(&member);
+                let member = tank.load_dependency(&member, &directory, profile_name)?;
+                tank.members.push(member);
+            }
+        }
+
+        // Commits are pinned as soon as the whole tree of dependencies is resolved
+        if parent.is_none() && !options.dry_run {
+            tank.lockfile
+                .borrow()
+                .save()
+                .map_err(|err| TankError::DependencyError(format!("Failed to write 'abs.lock': {err}")))?;
+        }
 
         let mut sections_config = config.get_mut("sections");
 
-        let mut sections: Vec<Rc<RefCell<Section>>> = vec![];
-        if let Some(sections_config) = sections_config {
-            if let toml::Value::Table(t) = sections_config {
-                for (key, value) in t {
-                    sections.push(Rc::new(RefCell::new(
-                        Section::new(&tank, key.to_string(), &value, Rc::clone(&profile))
-                            .map_err(|err| TankError::SectionError(format!("{:#?}", err)))?,
-                    )));
+        // Sections are added one by one so pipes can refer to the ones created before
+        if let Some(toml::Value::Table(t)) = sections_config {
+            for key in Tank::order_sections(t)? {
+                let value = &t[&key];
+                let section = Section::new(&tank, key.to_string(), value, Rc::clone(&profile))
+                    .map_err(|err| TankError::SectionError(format!("{:#?}", err)))?;
+                tank.sections.push(Rc::new(RefCell::new(section)));
+            }
+        }
+
+        Ok(tank)
+    }
+
+    /// Orders sections so that every section goes after the sections it pipes
+    fn order_sections(sections: &toml::value::Table) -> Result<Vec<String>, TankError> {
+        fn visit(
+            name: &str,
+            sections: &toml::value::Table,
+            visiting: &mut Vec<String>,
+            ordered: &mut Vec<String>,
+        ) -> Result<(), TankError> {
+            if ordered.iter().any(|section| section == name) {
+                return Ok(());
+            }
+            if visiting.iter().any(|section| section == name) {
+                visiting.push(name.to_string());
+                return Err(TankError::SectionError(format!(
+                    "Pipes form a cycle: {}",
+                    visiting.join(" -> ")
+                )));
+            }
+            visiting.push(name.to_string());
+            let pipes = sections[name]
+                .get("pipes")
+                .and_then(|pipes| pipes.as_array())
+                .cloned()
+                .unwrap_or_default();
+            for pipe in pipes.iter().filter_map(|pipe| pipe.as_str()) {
+                if let Some(piped) = pipe.strip_prefix("sections.") {
+                    if sections.contains_key(piped) {
+                        visit(piped, sections, visiting, ordered)?;
+                    }
+                }
+            }
+            visiting.pop();
+            ordered.push(name.to_string());
+            Ok(())
+        }
+
+        let mut ordered = vec![];
+        for name in sections.keys() {
+            visit(name, sections, &mut vec![], &mut ordered)?;
+        }
+        Ok(ordered)
+    }
+
+    /// Build directory from the command line or environment goes first, then
+    /// 'build_directory' of the configuration file and `.abs` next to it
+    fn get_root_build_directory(config: &toml::Value, root: &str, options: &Options) -> Result<String, TankError> {
+        if let Some(build_directory) = &options.build_directory {
+            return Ok(build_directory.clone());
+        }
+        for table in ["tank", "workspace"] {
+            if let Some(build_directory) = config.get(table).and_then(|table| table.get("build_directory")) {
+                let build_directory = build_directory.as_str().ok_or_else(|| {
+                    TankError::WrongTypeOfField(format!("'{table}.build_directory' is string type"))
+                })?;
+                return Ok(if Path::new(build_directory).is_absolute() {
+                    build_directory.to_string()
+                } else {
+                    format!("{root}/{build_directory}")
+                });
+            }
+        }
+        Ok(format!("{root}/.abs"))
+    }
+
+    /// Members of the workspace are loaded once, dependents of a member share it
+    fn load_dependency(&self, name: &str, directory: &str, profile_name: &str) -> Result<Rc<Tank>, TankError> {
+        let config_name = format!("{directory}/abs.toml");
+        let manifest = Path::new(&config_name).canonicalize().ok();
+        if let Some(member) = manifest.as_deref().and_then(|manifest| self.workspace.get_loaded(manifest)) {
+            return Ok(member);
+        }
+        let dependency = Tank::load(&config_name, profile_name, Rc::clone(&self.options), Some((self, name)))
+            .map_err(|err| match err {
+                TankError::DependencyError(message) => {
+                    TankError::DependencyError(format!("'{name}' -> {message}"))
                 }
+                err => TankError::DependencyError(format!("'{name}': {:?}", err)),
+            })?;
+        let dependency = Rc::new(dependency);
+        if let Some(manifest) = &manifest {
+            self.workspace.add_loaded(manifest, &dependency);
+        }
+        Ok(dependency)
+    }
+
+    fn detect_toolchain(
+        config: &toml::Value,
+        root: &str,
+        options: &Options,
+        profile: &Profile,
+        build_directory: &str,
+    ) -> Result<Toolchain, TankError> {
+        let target = match &options.target {
+            Some(triple) => {
+                let target_config = config
+                    .get("targets")
+                    .and_then(|targets| targets.get(triple))
+                    .ok_or_else(|| {
+                        TankError::MandatoryLack(format!("Can't find 'targets.{triple}' table"))
+                    })?;
+                let mut target = Target::from_config(triple, target_config)
+                    .map_err(TankError::WrongTypeOfField)?;
+                // Relative sysroot is resolved against the manifest like include directories
+                target.sysroot = target.sysroot.map(|sysroot| {
+                    if Path::new(&sysroot).is_absolute() {
+                        sysroot
+                    } else {
+                        format!("{root}/{sysroot}")
+                    }
+                });
+                Some(target)
             }
+            None => None,
+        };
+
+        Toolchain::new(
+            config.get("toolchain"),
+            target,
+            profile,
+            options,
+            build_directory,
+        )
+            .map_err(|err| TankError::ToolchainError(format!("{:?}", err)))
+    }
+
+    /// Paths in the configuration file are relative to its directory
+    pub fn resolve_path(&self, path: &str) -> String {
+        if Path::new(path).is_absolute() {
+            path.to_string()
+        } else {
+            format!("{}/{}", self.root, path)
         }
-        tank.sections = sections;
+    }
 
-        return Ok(tank);
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_root(&self) -> &str {
+        &self.root
+    }
+
+    pub fn get_build_directory(&self) -> &str {
+        &self.build_directory
     }
 
     pub fn check(&self) -> bool {
-            self.sections.iter().all(|section| {
+        self.sections.iter().all(|section| {
+            let section = section.borrow_mut();
+            section.check()
+        }) && self.members.iter().all(|member| member.check())
+    }
+
+    pub fn get_options(&self) -> Rc<Options> {
+        Rc::clone(&self.options)
+    }
+
+    pub fn get_toolchain(&self) -> Rc<Toolchain> {
+        Rc::clone(&self.toolchain)
+    }
+
+    pub fn find_section(&self, name: &str) -> Option<Weak<RefCell<Section>>> {
+        self.sections
+            .iter()
+            .find(|section| section.borrow().name == name)
+            .map(Rc::downgrade)
+    }
+
+    pub fn get_package(&self, name: &str) -> Option<Rc<Package>> {
+        self.packages.get(name).cloned()
+    }
+
+    pub fn find_dependency_section(&self, dependency: &str, name: &str) -> Option<Weak<RefCell<Section>>> {
+        self.dependencies.get(dependency)?.find_section(name)
+    }
+
+    /// Builds library sections which can be piped by the tanks depending on this one
+    fn build_libraries(&self) -> bool {
+        if self.libraries_built.get() {
+            return true;
+        }
+        let is_built = self.build_dependencies()
+            && self.sections.iter().all(|section| {
                 let section = section.borrow_mut();
-                section.check()
-            })
+                section.outlet_type == "executable" || section.build()
+            });
+        self.libraries_built.set(is_built);
+        is_built
+    }
+
+    fn build_dependencies(&self) -> bool {
+        self.dependencies.iter().all(|(name, dependency)| {
+            if dependency.libraries_built.get() {
+                return true;
+            }
+            if !self.options.quiet {
+                println!(
+                    "{:>RESULT_BORDER_WIDTH$} dependency '{}'",
+                    "Building".cyan(),
+                    name
+                );
+            }
+            dependency.build_libraries()
+        })
     }
 
     pub fn get_sections(&self) -> Vec<Weak<RefCell<Section>>> {
-        self.sections.iter().map(|elem|Rc::<RefCell<Section>>::downgrade(elem)).collect()
+        self.sections.iter().map(Rc::<RefCell<Section>>::downgrade).collect()
+    }
+
+    fn print_member(&self) {
+        if !self.options.quiet {
+            println!(
+                "{:>RESULT_BORDER_WIDTH$} member '{}'",
+                "Building".cyan(),
+                self.name
+            );
+        }
     }
 
     pub fn build(&self) -> bool {
-        self.sections.iter().all(|section| {
+        self.build_libraries() && self.sections.iter().all(|section| {
             let section = section.borrow_mut();
-            section.build()
+            section.outlet_type != "executable" || section.build()
+        }) && self.members.iter().all(|member| {
+            member.print_member();
+            member.build()
         })
     }
 
     pub fn run(&self) -> bool {
-        self.sections.iter().all(|section| {
+        self.build_dependencies() && self.sections.iter().all(|section| {
             let section = section.borrow_mut();
             section.run()
+        }) && self.members.iter().all(|member| {
+            member.print_member();
+            member.run()
         })
     }
 
+    /// Configuration files of the tank, its dependencies and members
+    fn get_manifests(&self) -> Vec<String> {
+        self.ancestors
+            .last()
+            .map(|manifest| manifest.to_string_lossy().to_string())
+            .into_iter()
+            .chain(self.dependencies.values().flat_map(|dependency| dependency.get_manifests()))
+            .chain(self.members.iter().flat_map(|member| member.get_manifests()))
+            .collect()
+    }
+
+    /// Canonical paths of configuration files and files of all sections
+    pub fn get_watched_files(&self) -> Vec<String> {
+        let mut files = self.get_manifests();
+        files.extend(self.get_section_files());
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    fn get_section_files(&self) -> Vec<String> {
+        self.sections
+            .iter()
+            .flat_map(|section| section.borrow().get_watched_files())
+            .chain(self.dependencies.values().flat_map(|dependency| dependency.get_section_files()))
+            .chain(self.members.iter().flat_map(|member| member.get_section_files()))
+            .collect()
+    }
+
+    /// Updates sections after the files changed. Returns false when the tank has to be
+    /// loaded again: a configuration file changed, a file disappeared or a new one appeared.
+    pub fn refresh(&self, changed: &[String]) -> bool {
+        let manifests = self.get_manifests();
+        let files = self.get_section_files();
+        if changed
+            .iter()
+            .any(|file| manifests.contains(file) || !files.contains(file))
+        {
+            return false;
+        }
+        self.refresh_sections(changed)
+    }
+
+    fn refresh_sections(&self, changed: &[String]) -> bool {
+        self.libraries_built.set(false);
+        self.sections
+            .iter()
+            .all(|section| section.borrow_mut().refresh(changed))
+            && self.dependencies.values().all(|dependency| dependency.refresh_sections(changed))
+            && self.members.iter().all(|member| member.refresh_sections(changed))
+    }
+
+    /// Starts executables of the tank and its members without waiting for them
+    pub fn spawn(&self) -> Vec<std::process::Child> {
+        self.sections
+            .iter()
+            .filter(|section| section.borrow().outlet_type == "executable")
+            .filter_map(|section| section.borrow().spawn())
+            .chain(self.members.iter().flat_map(|member| member.spawn()))
+            .collect()
+    }
+
+    /// Sections of the tank and its members
+    fn collect_sections(&self) -> Vec<Rc<RefCell<Section>>> {
+        self.sections
+            .iter()
+            .cloned()
+            .chain(self.members.iter().flat_map(|member| member.collect_sections()))
+            .collect()
+    }
+
+    pub fn get_section_graph(&self) -> Graph {
+        let mut graph = Graph::new("sections");
+        for section in self.collect_sections() {
+            let section = section.borrow();
+            graph.add_node(&section.get_qualified_name());
+            for pipe in section.get_pipe_names() {
+                graph.add_edge(&section.get_qualified_name(), &pipe);
+            }
+        }
+        graph
+    }
+
+    pub fn get_include_graph(&self) -> Graph {
+        let mut graph = Graph::new("includes");
+        for section in self.collect_sections() {
+            for (file, header) in section.borrow().get_include_edges() {
+                graph.add_edge(&file, &header);
+            }
+        }
+        graph
+    }
+
+    /// Translation units of all sections which are compiled again after the header changes
+    pub fn get_rebuilt_units(&self, header: &str) -> Option<Vec<String>> {
+        let header = Path::new(header).canonicalize().ok()?;
+        let mut units: Option<Vec<String>> = None;
+        for section in self.collect_sections() {
+            if let Some(section_units) = section.borrow().get_rebuilt_units(&header) {
+                units.get_or_insert_with(Vec::new).extend(section_units);
+            }
+        }
+        if let Some(units) = &mut units {
+            units.sort();
+            units.dedup();
+        }
+        units
+    }
+
     pub fn print_sections(&self) {
         for section in &self.sections {
             println!("{:#?}\n", section);
@@ -0,0 +1,39 @@
+use super::config;
+
+/// Description of a platform from `[targets.<triple>]` table
+#[derive(Debug, Clone, Default)]
+pub struct Target {
+    pub triple: String,
+    pub compiler: Option<String>,
+    pub c_compiler: Option<String>,
+    pub archiver: Option<String>,
+    pub sysroot: Option<String>,
+    pub options: Vec<String>,
+    pub linking_options: Vec<String>,
+}
+
+impl Target {
+    pub fn from_config(triple: &str, config: &toml::Value) -> Result<Target, String> {
+        Ok(Target {
+            triple: triple.to_string(),
+            compiler: config::get_string(config, "compiler")?,
+            c_compiler: config::get_string(config, "c_compiler")?,
+            archiver: config::get_string(config, "archiver")?,
+            sysroot: config::get_string(config, "sysroot")?,
+            options: config::get_strings(config, "options")?.unwrap_or_default(),
+            linking_options: config::get_strings(config, "linking_options")?.unwrap_or_default(),
+        })
+    }
+
+    /// Flags which are passed both to compiling and linking
+    pub fn get_flags(&self, is_clang: bool) -> Vec<String> {
+        let mut flags = vec![];
+        if is_clang {
+            flags.push(format!("--target={}", self.triple));
+        }
+        if let Some(sysroot) = &self.sysroot {
+            flags.push(format!("--sysroot={sysroot}"));
+        }
+        flags
+    }
+}
@@ -0,0 +1,136 @@
+use std::time::{Duration, Instant};
+
+use super::json;
+use super::section::RESULT_BORDER_WIDTH;
+use colored::Colorize;
+
+/// Number of the slowest translation units shown in the summary
+pub const SLOWEST_NUMBER: usize = 5;
+
+/// Durations of processes spawned while building a section
+#[derive(Debug)]
+pub struct Timings {
+    started: Instant,
+    compiled: Vec<(String, Duration)>,
+    linked: Option<(String, Duration)>,
+    /// Units restored from the shared cache instead of being compiled
+    cached: usize,
+    skipped: usize,
+}
+
+impl Timings {
+    pub fn new() -> Timings {
+        Timings {
+            started: Instant::now(),
+            compiled: vec![],
+            linked: None,
+            cached: 0,
+            skipped: 0,
+        }
+    }
+
+    pub fn record_compiling(&mut self, path: &str, duration: Duration) {
+        self.compiled.push((path.to_string(), duration));
+    }
+
+    pub fn record_linking(&mut self, path: &str, duration: Duration) {
+        self.linked = Some((path.to_string(), duration));
+    }
+
+    pub fn set_cached(&mut self, cached: usize) {
+        self.cached = cached;
+    }
+
+    pub fn set_skipped(&mut self, skipped: usize) {
+        self.skipped = skipped;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.compiled.is_empty() && self.cached == 0 && self.linked.is_none()
+    }
+
+    pub fn get_slowest(&self, number: usize) -> Vec<(String, Duration)> {
+        let mut slowest = self.compiled.clone();
+        slowest.sort_by(|left, right| right.1.cmp(&left.1).then_with(|| left.0.cmp(&right.0)));
+        slowest.truncate(number);
+        slowest
+    }
+
+    pub fn print_summary(&self) {
+        println!(
+            "{:>RESULT_BORDER_WIDTH$} in {:.2}s, compiled {}, cached {}, skipped {}",
+            "Finished".green().bold(),
+            self.started.elapsed().as_secs_f64(),
+            self.compiled.len(),
+            self.cached,
+            self.skipped
+        );
+        if let Some((_, duration)) = &self.linked {
+            println!(
+                "{:>RESULT_BORDER_WIDTH$} {:.2}s",
+                "Linking".cyan(),
+                duration.as_secs_f64()
+            );
+        }
+        if self.compiled.len() > 1 {
+            for (path, duration) in self.get_slowest(SLOWEST_NUMBER) {
+                println!(
+                    "{:>RESULT_BORDER_WIDTH$} {:.2}s '{}'",
+                    "Slowest".yellow(),
+                    duration.as_secs_f64(),
+                    path
+                );
+            }
+        }
+    }
+
+    pub fn to_json(&self, section: &str, profile: &str) -> String {
+        let compiled: Vec<String> = self
+            .compiled
+            .iter()
+            .map(|(path, duration)| {
+                format!(
+                    "    {{ \"file\": \"{}\", \"seconds\": {:.6} }}",
+                    json::escape(path),
+                    duration.as_secs_f64()
+                )
+            })
+            .collect();
+        let linked = match &self.linked {
+            Some((path, duration)) => format!(
+                "{{ \"file\": \"{}\", \"seconds\": {:.6} }}",
+                json::escape(path),
+                duration.as_secs_f64()
+            ),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\n  \"section\": \"{}\",\n  \"profile\": \"{}\",\n  \"seconds\": {:.6},\n  \"cached\": {},\n  \"skipped\": {},\n  \"compiled\": [\n{}\n  ],\n  \"linked\": {}\n}}\n",
+            json::escape(section),
+            json::escape(profile),
+            self.started.elapsed().as_secs_f64(),
+            self.cached,
+            self.skipped,
+            compiled.join(",\n"),
+            linked
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn slowest_files() {
+        let mut timings = Timings::new();
+        timings.record_compiling("a.cpp", Duration::from_millis(10));
+        timings.record_compiling("b.cpp", Duration::from_millis(30));
+        timings.record_compiling("c.cpp", Duration::from_millis(20));
+        let slowest: Vec<String> = timings
+            .get_slowest(2)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(slowest, vec!["b.cpp", "c.cpp"]);
+    }
+}
@@ -0,0 +1,439 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::UNIX_EPOCH;
+
+use super::config;
+use super::file::File;
+use super::options::Options;
+use super::profile::Profile;
+use super::section::RESULT_BORDER_WIDTH;
+use super::target::Target;
+use colored::Colorize;
+
+#[allow(unused)]
+#[derive(Debug)]
+pub enum ToolchainError {
+    WrongType(String),
+    ProbeFailed(String),
+    MissingCompiler(String),
+    MissingLauncher(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompilerKind {
+    Gcc,
+    Clang,
+    Unknown,
+}
+
+impl CompilerKind {
+    fn from_version_output(output: &str) -> CompilerKind {
+        let output = output.to_lowercase();
+        if output.contains("clang") {
+            CompilerKind::Clang
+        } else if output.contains("gcc")
+            || output.contains("g++")
+            || output.contains("free software foundation")
+        {
+            CompilerKind::Gcc
+        } else {
+            CompilerKind::Unknown
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CompilerKind::Gcc => "gcc",
+            CompilerKind::Clang => "clang",
+            CompilerKind::Unknown => "unknown",
+        }
+    }
+
+    fn from_str(kind: &str) -> CompilerKind {
+        match kind {
+            "gcc" => CompilerKind::Gcc,
+            "clang" => CompilerKind::Clang,
+            _ => CompilerKind::Unknown,
+        }
+    }
+}
+
+/// Information about the compiler that was obtained by running it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Probe {
+    pub kind: CompilerKind,
+    pub version: String,
+    pub target: String,
+    pub include_directories: Vec<String>,
+}
+
+/// Programs used for building and the facts about them.
+///
+/// Compilers are chosen in the following order: target, profile,
+/// `CXX`/`CC`/`AR` environment variables, `[toolchain]` table, system defaults.
+#[derive(Debug, Clone)]
+pub struct Toolchain {
+    pub compiler: String,
+    pub c_compiler: String,
+    pub archiver: String,
+    /// Program with its arguments which compilers are run through
+    pub launcher: Vec<String>,
+    pub compiler_path: Option<PathBuf>,
+    pub target: Option<Target>,
+    pub probe: Probe,
+    /// Set by `cache = true`, enables the shared object cache
+    pub cache: bool,
+}
+
+impl Toolchain {
+    pub fn new(
+        config: Option<&toml::Value>,
+        target: Option<Target>,
+        profile: &Profile,
+        options: &Options,
+        build_directory: &str,
+    ) -> Result<Toolchain, ToolchainError> {
+        let get = |key: &str| -> Result<Option<String>, ToolchainError> {
+            match config {
+                Some(config) => config::get_string(config, key).map_err(ToolchainError::WrongType),
+                None => Ok(None),
+            }
+        };
+        let from_environment = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let from_target = |program: fn(&Target) -> Option<String>| target.as_ref().and_then(program);
+
+        let compiler = from_target(|target| target.compiler.clone())
+            .or_else(|| profile.compiler.clone())
+            .or_else(|| from_environment("CXX"))
+            .or(get("compiler")?)
+            .unwrap_or_else(|| "c++".to_string());
+        let c_compiler = match &target {
+            Some(Target { compiler: Some(compiler), c_compiler: None, triple, .. }) => {
+                // Host C compiler can't build for another target
+                Some(Toolchain::derive_c_compiler(compiler).ok_or_else(|| {
+                    ToolchainError::MissingCompiler(format!(
+                        "Can't derive C compiler from '{compiler}', set 'targets.{triple}.c_compiler'"
+                    ))
+                })?)
+            }
+            _ => from_target(|target| target.c_compiler.clone()),
+        };
+        let c_compiler = c_compiler
+            .or_else(|| from_environment("CC"))
+            .or(get("c_compiler")?)
+            .unwrap_or_else(|| "cc".to_string());
+        let archiver = from_target(|target| target.archiver.clone())
+            .or_else(|| profile.archiver.clone())
+            .or_else(|| from_environment("AR"))
+            .or(get("archiver")?)
+            .unwrap_or_else(|| "ar".to_string());
+        let cache = match config.and_then(|config| config.get("cache")) {
+            Some(cache) => cache
+                .as_bool()
+                .ok_or_else(|| ToolchainError::WrongType("'cache' is boolean type".to_string()))?,
+            None => false,
+        };
+        let launcher: Vec<String> = options
+            .launcher
+            .clone()
+            .or_else(|| profile.compiler_launcher.clone())
+            .or(get("compiler_launcher")?)
+            .map(|launcher| launcher.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default();
+        if let Some(program) = launcher.first() {
+            if Toolchain::find_program(program).is_none() {
+                return Err(ToolchainError::MissingLauncher(format!("Can't find compiler launcher '{program}'")));
+            }
+        }
+
+        let compiler_path = Toolchain::find_program(&compiler);
+        let cache_path = Toolchain::get_cache_path(build_directory, &compiler, target.as_ref());
+        let probe = Toolchain::load_cached_probe(&cache_path, compiler_path.as_deref(), target.as_ref())
+            .map(Ok)
+            .unwrap_or_else(|| {
+                let probe = Toolchain::probe(&compiler, target.as_ref())?;
+                // Dry run leaves the build directory untouched
+                if !options.dry_run {
+                    Toolchain::store_probe(&cache_path, compiler_path.as_deref(), target.as_ref(), &probe);
+                }
+                Ok(probe)
+            })?;
+
+        Ok(Toolchain {
+            compiler,
+            c_compiler,
+            archiver,
+            launcher,
+            compiler_path,
+            target,
+            probe,
+            cache,
+        })
+    }
+
+    /// Command running the compiler through the launcher if there is one.
+    /// Archiver isn't run through it as launchers cache only compilers.
+    pub fn get_command(&self, compiler: &str) -> Command {
+        match self.launcher.split_first() {
+            Some((launcher, arguments)) => {
+                let mut command = Command::new(launcher);
+                command.args(arguments).arg(compiler);
+                command
+            }
+            None => Command::new(compiler),
+        }
+    }
+
+    /// Flags of the target which are passed to every compiler invocation
+    pub fn get_compiling_flags(&self) -> Vec<String> {
+        match &self.target {
+            Some(target) => target
+                .get_flags(self.probe.kind == CompilerKind::Clang)
+                .into_iter()
+                .chain(target.options.iter().cloned())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Flags of the target which are passed to the linker
+    pub fn get_linking_flags(&self) -> Vec<String> {
+        match &self.target {
+            Some(target) => target
+                .get_flags(self.probe.kind == CompilerKind::Clang)
+                .into_iter()
+                .chain(target.linking_options.iter().cloned())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn get_compiler_for(&self, file: &File) -> &str {
+        if file.path.ends_with(".c") {
+            &self.c_compiler
+        } else {
+            &self.compiler
+        }
+    }
+
+    /// C compiler of the same family and prefix, `aarch64-linux-gnu-g++` gives `aarch64-linux-gnu-gcc`
+    fn derive_c_compiler(compiler: &str) -> Option<String> {
+        let name_start = compiler.rfind('/').map(|position| position + 1).unwrap_or(0);
+        let (directory, name) = compiler.split_at(name_start);
+        [("clang++", "clang"), ("g++", "gcc"), ("c++", "cc")]
+            .iter()
+            .find_map(|(cpp, c)| {
+                name.rfind(cpp)
+                    .map(|position| format!("{directory}{}{c}{}", &name[..position], &name[position + cpp.len()..]))
+            })
+    }
+
+    fn find_program(program: &str) -> Option<PathBuf> {
+        if program.contains('/') {
+            return Path::new(program).canonicalize().ok();
+        }
+        std::env::split_paths(&std::env::var_os("PATH")?)
+            .map(|directory| directory.join(program))
+            .find(|candidate| candidate.is_file())
+            .and_then(|candidate| candidate.canonicalize().ok())
+    }
+
+    fn get_modification_stamp(path: &Path) -> Option<i64> {
+        let modified = path.metadata().ok()?.modified().ok()?;
+        Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos() as i64)
+    }
+
+    fn get_cache_path(build_directory: &str, compiler: &str, target: Option<&Target>) -> String {
+        let target = target.map(|target| target.triple.as_str()).unwrap_or("host");
+        format!("{}/toolchain/{}/{}.toml", build_directory, target, File::encode_path(compiler))
+    }
+
+    fn get_sysroot(target: Option<&Target>) -> &str {
+        target
+            .and_then(|target| target.sysroot.as_deref())
+            .unwrap_or_default()
+    }
+
+    fn load_cached_probe(cache_path: &str, compiler_path: Option<&Path>, target: Option<&Target>) -> Option<Probe> {
+        let compiler_path = compiler_path?;
+        let content = std::fs::read_to_string(cache_path).ok()?;
+        let cache: toml::Value = toml::from_str(&content).ok()?;
+
+        if cache.get("path")?.as_str()? != compiler_path.to_str()?
+            || cache.get("modified")?.as_integer()? != Toolchain::get_modification_stamp(compiler_path)?
+            || cache.get("sysroot")?.as_str()? != Toolchain::get_sysroot(target)
+        {
+            return None;
+        }
+        Some(Probe {
+            kind: CompilerKind::from_str(cache.get("kind")?.as_str()?),
+            version: cache.get("version")?.as_str()?.to_string(),
+            target: cache.get("target")?.as_str()?.to_string(),
+            include_directories: cache
+                .get("include_directories")?
+                .as_array()?
+                .iter()
+                .map(|directory| directory.as_str().map(str::to_string))
+                .collect::<Option<Vec<String>>>()?,
+        })
+    }
+
+    fn store_probe(cache_path: &str, compiler_path: Option<&Path>, target: Option<&Target>, probe: &Probe) {
+        let compiler_path = match compiler_path {
+            Some(path) => path,
+            None => return,
+        };
+        let (path, modified) = match (
+            compiler_path.to_str(),
+            Toolchain::get_modification_stamp(compiler_path),
+        ) {
+            (Some(path), Some(modified)) => (path, modified),
+            _ => return,
+        };
+        let mut cache = toml::value::Table::new();
+        cache.insert("path".to_string(), toml::Value::String(path.to_string()));
+        cache.insert("modified".to_string(), toml::Value::Integer(modified));
+        cache.insert("sysroot".to_string(), toml::Value::String(Toolchain::get_sysroot(target).to_string()));
+        cache.insert("kind".to_string(), toml::Value::String(probe.kind.as_str().to_string()));
+        cache.insert("version".to_string(), toml::Value::String(probe.version.clone()));
+        cache.insert("target".to_string(), toml::Value::String(probe.target.clone()));
+        cache.insert(
+            "include_directories".to_string(),
+            toml::Value::Array(
+                probe
+                    .include_directories
+                    .iter()
+                    .map(|directory| toml::Value::String(directory.clone()))
+                    .collect(),
+            ),
+        );
+        if let Some(parent) = Path::new(cache_path).parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Ok(content) = toml::to_string(&toml::Value::Table(cache)) {
+            let _ = std::fs::write(cache_path, content);
+        }
+    }
+
+    pub fn probe(compiler: &str, target: Option<&Target>) -> Result<Probe, ToolchainError> {
+        let run = |flags: &[String], args: &[&str]| {
+            Command::new(compiler)
+                .args(flags)
+                .args(args)
+                .output()
+                .map_err(|err| ToolchainError::ProbeFailed(format!("Can't run '{compiler}': {err}")))
+        };
+
+        let version_output = String::from_utf8_lossy(&run(&[], &["--version"])?.stdout).to_string();
+        let kind = CompilerKind::from_version_output(&version_output);
+        let version = Toolchain::parse_version(version_output.lines().next().unwrap_or_default());
+
+        let flags = target
+            .map(|target| target.get_flags(kind == CompilerKind::Clang))
+            .unwrap_or_default();
+
+        let target = String::from_utf8_lossy(&run(&flags, &["-dumpmachine"])?.stdout)
+            .trim()
+            .to_string();
+
+        let search_output = run(&flags, &["-xc++", "-E", "-Wp,-v", "/dev/null"])?;
+        let include_directories =
+            Toolchain::parse_include_directories(&String::from_utf8_lossy(&search_output.stderr));
+
+        Ok(Probe {
+            kind,
+            version,
+            target,
+            include_directories,
+        })
+    }
+
+    fn parse_version(line: &str) -> String {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let candidate = tokens
+            .iter()
+            .position(|token| *token == "version")
+            .and_then(|position| tokens.get(position + 1))
+            .or_else(|| tokens.last())
+            .copied()
+            .unwrap_or_default();
+        candidate
+            .chars()
+            .take_while(|symbol| symbol.is_ascii_digit() || *symbol == '.')
+            .collect()
+    }
+
+    fn parse_include_directories(output: &str) -> Vec<String> {
+        output
+            .lines()
+            .skip_while(|line| !line.starts_with("#include <...> search starts here:"))
+            .skip(1)
+            .take_while(|line| !line.starts_with("End of search list."))
+            .map(|line| line.trim().trim_end_matches(" (framework directory)").to_string())
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+
+    pub fn print(&self) {
+        let compiler_path = self
+            .compiler_path
+            .as_ref()
+            .and_then(|path| path.to_str())
+            .unwrap_or("not found");
+        println!("{:>RESULT_BORDER_WIDTH$} {} ({})", "Compiler".cyan(), self.compiler, compiler_path);
+        println!("{:>RESULT_BORDER_WIDTH$} {}", "Kind".cyan(), self.probe.kind.as_str());
+        println!("{:>RESULT_BORDER_WIDTH$} {}", "Version".cyan(), self.probe.version);
+        println!("{:>RESULT_BORDER_WIDTH$} {}", "Target".cyan(), self.probe.target);
+        println!("{:>RESULT_BORDER_WIDTH$} {}", "C compiler".cyan(), self.c_compiler);
+        println!("{:>RESULT_BORDER_WIDTH$} {}", "Archiver".cyan(), self.archiver);
+        if !self.launcher.is_empty() {
+            println!("{:>RESULT_BORDER_WIDTH$} {}", "Launcher".cyan(), self.launcher.join(" "));
+        }
+        if let Some(sysroot) = self.target.as_ref().and_then(|target| target.sysroot.as_ref()) {
+            println!("{:>RESULT_BORDER_WIDTH$} {}", "Sysroot".cyan(), sysroot);
+        }
+        for (index, directory) in self.probe.include_directories.iter().enumerate() {
+            let label = if index == 0 { "Includes" } else { "" };
+            println!("{:>RESULT_BORDER_WIDTH$} {}", label.cyan(), directory);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_version() {
+        assert_eq!(Toolchain::parse_version("g++ (Debian 12.2.0-14) 12.2.0"), "12.2.0");
+        assert_eq!(Toolchain::parse_version("Ubuntu clang version 14.0.0-1ubuntu1"), "14.0.0");
+        assert_eq!(
+            CompilerKind::from_version_output("Apple clang version 15.0.0 (clang-1500.1.0.2.5)"),
+            CompilerKind::Clang
+        );
+        assert_eq!(CompilerKind::from_version_output("g++ (GCC) 13.2.1"), CompilerKind::Gcc);
+    }
+
+    #[test]
+    fn deriving_c_compiler() {
+        assert_eq!(Toolchain::derive_c_compiler("aarch64-linux-gnu-g++").as_deref(), Some("aarch64-linux-gnu-gcc"));
+        assert_eq!(Toolchain::derive_c_compiler("/opt/llvm/bin/clang++-15").as_deref(), Some("/opt/llvm/bin/clang-15"));
+        assert_eq!(Toolchain::derive_c_compiler("x86_64-w64-mingw32-c++").as_deref(), Some("x86_64-w64-mingw32-cc"));
+        assert_eq!(Toolchain::derive_c_compiler("icpx"), None);
+    }
+
+    #[test]
+    fn parsing_include_directories() {
+        let output = "ignoring nonexistent directory \"/usr/local/include/x86_64-linux-gnu\"\n\
+                      #include \"...\" search starts here:\n\
+                      #include <...> search starts here:\n \
+                      /usr/include/c++/12\n \
+                      /usr/include\n \
+                      /System/Library/Frameworks (framework directory)\n\
+                      End of search list.\n";
+        assert_eq!(
+            Toolchain::parse_include_directories(output),
+            vec!["/usr/include/c++/12", "/usr/include", "/System/Library/Frameworks"]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs;
+
+use super::file::File;
+
+/// Number of sources included by a single unity file unless the section sets it
+pub const DEFAULT_BATCH_SIZE: usize = 8;
+
+/// Sources of a section grouped into generated `unity_N.cpp` files
+/// which include them and are compiled instead of them
+#[derive(Debug)]
+pub struct Unity {
+    directory: String,
+    batches: Vec<Vec<File>>,
+}
+
+impl Unity {
+    pub fn new(sources: &[File], batch_size: usize, directory: &str) -> Unity {
+        Unity {
+            directory: directory.to_string(),
+            batches: sources
+                .chunks(batch_size.max(1))
+                .map(|batch| batch.to_vec())
+                .collect(),
+        }
+    }
+
+    pub fn find_batch(&self, file: &File) -> Option<usize> {
+        self.batches.iter().position(|batch| batch.contains(file))
+    }
+
+    pub fn get_batch(&self, index: usize) -> &[File] {
+        &self.batches[index]
+    }
+
+    /// Replaces sources by their versions with new modification times
+    pub fn update(&mut self, update: impl Fn(&File) -> File) {
+        for batch in &mut self.batches {
+            *batch = batch.iter().map(&update).collect();
+        }
+    }
+
+    pub fn get_batch_path(&self, index: usize) -> String {
+        format!("{}/unity_{}.cpp", self.directory, index)
+    }
+
+    pub fn get_object_path(&self, index: usize, artifacts_directory: &str) -> String {
+        format!("{artifacts_directory}/binary/unity_{index}.o")
+    }
+
+    fn get_content(batch: &[File]) -> String {
+        batch
+            .iter()
+            .map(|file| format!("#include \"{}\"\n", file.path))
+            .collect()
+    }
+
+    /// Batches which unity files don't include the current set of sources yet
+    pub fn get_changed(&self) -> Vec<usize> {
+        (0..self.batches.len())
+            .filter(|&index| {
+                let content = Unity::get_content(&self.batches[index]);
+                fs::read_to_string(self.get_batch_path(index)).ok().as_deref() != Some(content.as_str())
+            })
+            .collect()
+    }
+
+    /// Unity files are rewritten only when the set of sources changes.
+    /// Returns batches which were rewritten and have to be compiled again.
+    pub fn write(&self) -> std::io::Result<Vec<usize>> {
+        fs::create_dir_all(&self.directory)?;
+        let changed = self.get_changed();
+        for &index in &changed {
+            fs::write(self.get_batch_path(index), Unity::get_content(&self.batches[index]))?;
+        }
+        Ok(changed)
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecursiveMode};
+
+/// Time given to an editor to finish saving, events within it are reported together
+const SETTLING_TIME: Duration = Duration::from_millis(100);
+
+/// Waits for changes of files used by a tank. Events are queued from the moment
+/// the watcher is created, so changes made during a build aren't lost.
+pub struct Watcher {
+    files: HashSet<PathBuf>,
+    directories: HashSet<PathBuf>,
+    watcher: Box<dyn notify::Watcher>,
+    events: Receiver<notify::Result<Event>>,
+}
+
+impl Watcher {
+    /// Uses the native mechanism of the platform and polls modification times when it's unavailable
+    pub fn new(files: &[String]) -> Result<Watcher, String> {
+        let (sender, events) = channel();
+        let watcher: Box<dyn notify::Watcher> = match notify::recommended_watcher(sender.clone()) {
+            Ok(watcher) => Box::new(watcher),
+            Err(_) => Box::new(
+                notify::PollWatcher::new(sender, notify::Config::default().with_poll_interval(SETTLING_TIME))
+                    .map_err(|err| err.to_string())?,
+            ),
+        };
+        let mut watcher = Watcher {
+            files: HashSet::new(),
+            directories: HashSet::new(),
+            watcher,
+            events,
+        };
+        watcher.watch(files)?;
+        Ok(watcher)
+    }
+
+    /// Replaces the watched files. Directories are watched instead of files, so files
+    /// replaced by editors and newly created sources are noticed as well.
+    pub fn watch(&mut self, files: &[String]) -> Result<(), String> {
+        self.files = files
+            .iter()
+            .filter_map(|file| Path::new(file).canonicalize().ok())
+            .collect();
+        let directories: HashSet<PathBuf> = self
+            .files
+            .iter()
+            .filter_map(|file| file.parent().map(Path::to_path_buf))
+            .collect();
+        for directory in self.directories.difference(&directories) {
+            let _ = self.watcher.unwatch(directory);
+        }
+        for directory in directories.difference(&self.directories) {
+            self.watcher
+                .watch(directory, RecursiveMode::NonRecursive)
+                .map_err(|err| format!("Can't watch '{}': {}", directory.to_string_lossy(), err))?;
+        }
+        self.directories = directories;
+        Ok(())
+    }
+
+    /// Sources and configuration files which appear in watched directories are relevant too
+    fn is_relevant(&self, path: &Path) -> bool {
+        self.files.contains(path)
+            || path
+                .file_name()
+                .map(|name| name.to_string_lossy())
+                .map(|name| {
+                    name == "abs.toml" || [".hpp", ".cpp", ".h", ".c"].iter().any(|suffix| name.ends_with(suffix))
+                })
+                .unwrap_or(false)
+    }
+
+    fn get_changed(&self, event: notify::Result<Event>) -> Vec<PathBuf> {
+        match event {
+            Ok(event) if !matches!(event.kind, EventKind::Access(_)) => event
+                .paths
+                .into_iter()
+                .filter(|path| self.is_relevant(path))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Blocks until relevant files change and returns them
+    pub fn wait(&mut self) -> Vec<String> {
+        let mut changed = vec![];
+        while changed.is_empty() {
+            match self.events.recv() {
+                Ok(event) => changed = self.get_changed(event),
+                Err(_) => return vec![],
+            }
+        }
+        // Editors write files in several steps, so the rest of events is collected as well
+        while let Ok(event) = self.events.recv_timeout(SETTLING_TIME) {
+            changed.extend(self.get_changed(event));
+        }
+        let mut changed: Vec<String> = changed
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("abs-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        directory.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn relevant_files() {
+        let directory = create_directory("relevant");
+        let source = directory.join("main.cpp");
+        let data = directory.join("data.bin");
+        std::fs::write(&source, "").unwrap();
+        std::fs::write(&data, "").unwrap();
+        let watcher = Watcher::new(&[data.to_string_lossy().to_string()]).unwrap();
+        assert!(watcher.is_relevant(&data));
+        assert!(watcher.is_relevant(&source));
+        assert!(watcher.is_relevant(&directory.join("abs.toml")));
+        assert!(!watcher.is_relevant(&directory.join("notes.txt")));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn detecting_changes() {
+        let directory = create_directory("changes");
+        let source = directory.join("main.cpp");
+        std::fs::write(&source, "").unwrap();
+        let mut watcher = Watcher::new(&[source.to_string_lossy().to_string()]).unwrap();
+        // Changes made before waiting are reported as well
+        std::fs::write(directory.join("notes.txt"), "").unwrap();
+        std::fs::write(&source, "int main() {}\n").unwrap();
+        assert_eq!(watcher.wait(), vec![source.to_string_lossy().to_string()]);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
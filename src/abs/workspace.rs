@@ -0,0 +1,95 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+
+use super::tank::Tank;
+
+/// Tanks listed in `[workspace] members` of the root configuration file.
+/// They are built together and keep artifacts in the build directory of the root.
+#[derive(Debug, Default)]
+pub struct Workspace {
+    build_directory: String,
+    /// Configuration file of each member with the path it is listed by
+    members: Vec<(PathBuf, String)>,
+    /// Members which are already loaded, a member is loaded once even when other tanks depend on it
+    loaded: RefCell<HashMap<PathBuf, Weak<Tank>>>,
+}
+
+impl Workspace {
+    pub fn from_config(config: &toml::Value, root: &str, build_directory: &str) -> Result<Workspace, String> {
+        let mut workspace = Workspace {
+            build_directory: build_directory.to_string(),
+            members: vec![],
+            loaded: RefCell::new(HashMap::new()),
+        };
+        let members = match config.get("workspace").and_then(|workspace| workspace.get("members")) {
+            Some(members) => members
+                .as_array()
+                .ok_or("'workspace.members' is array type")?,
+            None => return Ok(workspace),
+        };
+        for member in members {
+            let member = member
+                .as_str()
+                .ok_or("'workspace.members' contains only strings")?
+                .trim_start_matches("./")
+                .trim_end_matches('/');
+            let manifest = Path::new(root)
+                .join(member)
+                .join("abs.toml")
+                .canonicalize()
+                .map_err(|err| format!("Can't find configuration file of member '{member}': {err}"))?;
+            workspace.members.push((manifest, member.to_string()));
+        }
+        Ok(workspace)
+    }
+
+    /// Paths of members as they are listed in the configuration file
+    pub fn get_members(&self) -> Vec<String> {
+        self.members.iter().map(|(_, member)| member.clone()).collect()
+    }
+
+    /// Build directory of the member with the given configuration file
+    pub fn get_member_directory(&self, manifest: &Path) -> Option<String> {
+        self.members
+            .iter()
+            .find(|(member_manifest, _)| member_manifest == manifest)
+            .map(|(_, member)| format!("{}/members/{}", self.build_directory, member))
+    }
+
+    /// Member with the given configuration file if it's loaded already
+    pub fn get_loaded(&self, manifest: &Path) -> Option<Rc<Tank>> {
+        self.loaded.borrow().get(manifest).and_then(Weak::upgrade)
+    }
+
+    /// Remembers the tank when it's a member, tanks which aren't members are loaded by each dependent
+    pub fn add_loaded(&self, manifest: &Path, tank: &Rc<Tank>) {
+        if self.get_member_directory(manifest).is_some() {
+            self.loaded.borrow_mut().insert(manifest.to_path_buf(), Rc::downgrade(tank));
+        }
+    }
+
+    /// Walks up from the directory of the configuration file and returns
+    /// the configuration file of the workspace which lists it as a member
+    pub fn find_root(manifest: &Path) -> Option<PathBuf> {
+        let mut directory = manifest.parent()?.parent();
+        while let Some(current) = directory {
+            let candidate = current.join("abs.toml");
+            if let Ok(content) = fs::read_to_string(&candidate) {
+                if let Ok(config) = toml::from_str::<toml::Value>(&content) {
+                    let root = current.to_string_lossy();
+                    let is_member = Workspace::from_config(&config, &root, "")
+                        .map(|workspace| workspace.get_member_directory(manifest).is_some())
+                        .unwrap_or(false);
+                    if is_member {
+                        return Some(candidate);
+                    }
+                }
+            }
+            directory = current.parent();
+        }
+        None
+    }
+}
@@ -1,10 +1,13 @@
 mod abs;
 
-use std::io::Write;
+use std::io::{IsTerminal, Write};
 
 use abs::prelude::*;
+use abs::json;
+use abs::section::RESULT_BORDER_WIDTH;
 
 use clap::{arg, Command, ArgMatches};
+use colored::Colorize;
 
 fn cli() -> Command {
     Command::new("abs")
@@ -13,6 +16,9 @@ fn cli() -> Command {
         .subcommand_required(true)
         .arg_required_else_help(true)
         .allow_external_subcommands(true)
+        .arg(arg!(-v --verbose... "Prints commands with working directories, twice also why units are compiled").global(true))
+        .arg(arg!(-q --quiet "Prints only errors").global(true).conflicts_with("verbose"))
+        .arg(arg!(--color <WHEN> "Colors the output, 'auto' does it on terminals unless NO_COLOR is set").value_parser(["auto", "always", "never"]).default_value("auto").global(true))
         .subcommand(
             Command::new("new")
                 .about("Creates new tank directory")
@@ -21,44 +27,270 @@ fn cli() -> Command {
         .subcommand(
             Command::new("check")
                 .about("Checks that the current section can be built")
-                .arg(arg!(-p --profile <PROFILE> "Sets profile for checking").required(false)),
+                .arg(arg!(-p --profile <PROFILE> "Sets profile for checking").required(false))
+                .arg(arg!(-j --jobs <N> "Number of compilers running at once").value_parser(clap::value_parser!(usize)).required(false))
+                .arg(arg!(--target <TRIPLE> "Builds for the target described in [targets.<TRIPLE>]").required(false))
+                .arg(arg!(--"manifest-path" <PATH> "Path to abs.toml instead of the nearest one").required(false))
+                .arg(arg!(--"build-dir" <DIRECTORY> "Directory for artifacts, overrides ABS_BUILD_DIR").required(false))
+                .arg(arg!(--launcher <PROGRAM> "Runs compilers through the program, e.g. ccache").required(false)),
         )
         .subcommand(
             Command::new("build")
                 .about("Builds the current section")
-                .arg(arg!(-p --profile <PROFILE> "Sets profile for building").required(false)),
+                .arg(arg!(-p --profile <PROFILE> "Sets profile for building").required(false))
+                .arg(arg!(-j --jobs <N> "Number of compilers running at once").value_parser(clap::value_parser!(usize)).required(false))
+                .arg(arg!(--target <TRIPLE> "Builds for the target described in [targets.<TRIPLE>]").required(false))
+                .arg(arg!(--"manifest-path" <PATH> "Path to abs.toml instead of the nearest one").required(false))
+                .arg(arg!(--"build-dir" <DIRECTORY> "Directory for artifacts, overrides ABS_BUILD_DIR").required(false))
+                .arg(arg!(--launcher <PROGRAM> "Runs compilers through the program, e.g. ccache").required(false))
+                .arg(arg!(--timings "Writes durations of compiling and linking to the build directory").required(false))
+                .arg(arg!(--unity "Compiles sources in batches included by generated unity files").required(false))
+                .arg(arg!(--"no-cache" "Always compiles instead of taking objects from the shared cache").required(false))
+                .arg(arg!(--"dry-run" "Prints commands of compiler, archiver and linker instead of running them").required(false))
+                .arg(arg!(--explain "Prints why every translation unit is compiled").required(false)),
+        )
+        .subcommand(
+            Command::new("watch")
+                .about("Builds, checks or runs again whenever sources or the configuration change")
+                .arg(arg!([command] "What to do after a change").value_parser(["build", "check", "run"]).default_value("build"))
+                .arg(arg!(-p --profile <PROFILE> "Sets profile for building").required(false))
+                .arg(arg!(-j --jobs <N> "Number of compilers running at once").value_parser(clap::value_parser!(usize)).required(false))
+                .arg(arg!(--target <TRIPLE> "Builds for the target described in [targets.<TRIPLE>]").required(false))
+                .arg(arg!(--"manifest-path" <PATH> "Path to abs.toml instead of the nearest one").required(false))
+                .arg(arg!(--"build-dir" <DIRECTORY> "Directory for artifacts, overrides ABS_BUILD_DIR").required(false))
+                .arg(arg!(--launcher <PROGRAM> "Runs compilers through the program, e.g. ccache").required(false))
+                .arg(arg!(--timings "Writes durations of compiling and linking to the build directory").required(false))
+                .arg(arg!(--unity "Compiles sources in batches included by generated unity files").required(false))
+                .arg(arg!(--"no-cache" "Always compiles instead of taking objects from the shared cache").required(false)),
+        )
+        .subcommand(
+            Command::new("graph")
+                .about("Prints pipes between sections and headers included by sources")
+                .arg(arg!(--sections "Prints pipes between sections").required(false))
+                .arg(arg!(--includes "Prints headers included by sources").required(false))
+                .arg(arg!(--format <FORMAT> "Format of the output").value_parser(["dot", "json"]).default_value("dot"))
+                .arg(arg!(--rdeps <HEADER> "Prints translation units compiled again after the header changes").required(false))
+                .arg(arg!(-p --profile <PROFILE> "Sets profile which sections are shown").required(false))
+                .arg(arg!(--target <TRIPLE> "Builds for the target described in [targets.<TRIPLE>]").required(false))
+                .arg(arg!(--"manifest-path" <PATH> "Path to abs.toml instead of the nearest one").required(false))
+                .arg(arg!(--"build-dir" <DIRECTORY> "Directory for artifacts, overrides ABS_BUILD_DIR").required(false)),
+        )
+        .subcommand(
+            Command::new("toolchain")
+                .about("Shows the detected compiler, archiver and default include directories")
+                .arg(arg!(-p --profile <PROFILE> "Sets profile which compiler is shown").required(false))
+                .arg(arg!(--target <TRIPLE> "Builds for the target described in [targets.<TRIPLE>]").required(false))
+                .arg(arg!(--"manifest-path" <PATH> "Path to abs.toml instead of the nearest one").required(false))
+                .arg(arg!(--"build-dir" <DIRECTORY> "Directory for artifacts, overrides ABS_BUILD_DIR").required(false))
+                .arg(arg!(--launcher <PROGRAM> "Runs compilers through the program, e.g. ccache").required(false)),
         )
         .subcommand(
             Command::new("run")
                 .about("Builds and runs")
-                .arg(arg!(-p --profile <PROFILE> "Sets profile for running").required(false)),
+                .arg(arg!(-p --profile <PROFILE> "Sets profile for running").required(false))
+                .arg(arg!(-j --jobs <N> "Number of compilers running at once").value_parser(clap::value_parser!(usize)).required(false))
+                .arg(arg!(--target <TRIPLE> "Builds for the target described in [targets.<TRIPLE>]").required(false))
+                .arg(arg!(--"manifest-path" <PATH> "Path to abs.toml instead of the nearest one").required(false))
+                .arg(arg!(--"build-dir" <DIRECTORY> "Directory for artifacts, overrides ABS_BUILD_DIR").required(false))
+                .arg(arg!(--launcher <PROGRAM> "Runs compilers through the program, e.g. ccache").required(false))
+                .arg(arg!(--timings "Writes durations of compiling and linking to the build directory").required(false))
+                .arg(arg!(--unity "Compiles sources in batches included by generated unity files").required(false))
+                .arg(arg!(--"no-cache" "Always compiles instead of taking objects from the shared cache").required(false))
+                .arg(arg!(--"dry-run" "Prints commands of compiler, archiver and linker instead of running them").required(false))
+                .arg(arg!(--explain "Prints why every translation unit is compiled").required(false)),
         )
 }
 
-fn get_tank(profile_name: &str) -> Tank {
-    Tank::new("abs.toml", profile_name).unwrap_or_else(|err| {
-        match err {
-            TankError::ConfigFileDoesntExist(_) => {
-                println!("Can't find configuration file. Check it")
-            }
-            TankError::WrongFormatOfToml(message) => {
-                println!("TOML format is wrong. Can't parse: {message}")
-            }
-            TankError::MandatoryLack(message) => println!(
-                "Can't find mandatory field in configuration file: {message}"
-                
-            ),
-            TankError::WrongTypeOfField(message) => {
-                println!("Type of field is wrong: {message}")
-            }
-            TankError::SectionError(message) => {
-                println!("Got error from section: {message}")
-            }
+/// Output redirected to a file or a log of CI gets neither colors nor the progress line
+fn is_terminal() -> bool {
+    std::io::stdout().is_terminal() && std::env::var("TERM").map(|term| term != "dumb").unwrap_or(true)
+}
+
+fn configure_colors(matches: &ArgMatches) {
+    let when = matches.get_one::<String>("color").map(String::as_str).unwrap_or("auto");
+    let is_colored = match when {
+        "always" => true,
+        "never" => false,
+        _ => std::env::var_os("NO_COLOR").map(|value| value.is_empty()).unwrap_or(true) && is_terminal(),
+    };
+    colored::control::set_override(is_colored);
+}
+
+fn get_options(matches: &ArgMatches) -> Options {
+    let is_set = |name: &str| {
+        matches
+            .try_get_one::<bool>(name)
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false)
+    };
+    // Not every command accepts all the options
+    let get_string = |name: &str| matches.try_get_one::<String>(name).ok().flatten().cloned();
+    let verbose = matches.try_get_one::<u8>("verbose").ok().flatten().copied().unwrap_or(0);
+    Options {
+        timings: is_set("timings"),
+        unity: is_set("unity"),
+        no_cache: is_set("no-cache"),
+        dry_run: is_set("dry-run"),
+        explain: is_set("explain") || verbose > 1,
+        verbose,
+        quiet: is_set("quiet"),
+        progress: is_terminal() && !is_set("quiet") && verbose == 0 && !is_set("dry-run"),
+        launcher: get_string("launcher"),
+        target: get_string("target"),
+        jobs: matches.try_get_one::<usize>("jobs").ok().flatten().copied(),
+        build_directory: get_string("build-dir")
+            .or_else(|| std::env::var("ABS_BUILD_DIR").ok().filter(|directory| !directory.is_empty())),
+    }
+}
+
+/// Finds the nearest abs.toml in the current directory or its parents
+fn find_manifest() -> Option<String> {
+    let current = std::env::current_dir().ok()?;
+    current
+        .ancestors()
+        .map(|directory| directory.join("abs.toml"))
+        .find(|manifest| manifest.is_file())
+        .map(|manifest| manifest.to_string_lossy().to_string())
+}
+
+fn get_manifest(matches: &ArgMatches) -> String {
+    match matches.get_one::<String>("manifest-path") {
+        Some(manifest) => manifest.clone(),
+        None => find_manifest().unwrap_or_else(|| {
+            println!("Can't find 'abs.toml' in the current directory or any parent directory");
+            std::process::exit(1)
+        }),
+    }
+}
+
+fn load_tank(manifest: &str, profile_name: &str, options: Options) -> Result<Tank, String> {
+    Tank::new(manifest, profile_name, options).map_err(|err| match err {
+        TankError::ConfigFileDoesntExist(message) => {
+            format!("Can't find configuration file '{manifest}': {message}")
+        }
+        TankError::WrongFormatOfToml(message) => {
+            format!("TOML format is wrong. Can't parse: {message}")
+        }
+        TankError::MandatoryLack(message) => {
+            format!("Can't find mandatory field in configuration file: {message}")
         }
+        TankError::WrongTypeOfField(message) => {
+            format!("Type of field is wrong: {message}")
+        }
+        TankError::SectionError(message) => {
+            format!("Got error from section: {message}")
+        }
+        TankError::ToolchainError(message) => {
+            format!("Failed to detect toolchain: {message}")
+        }
+        TankError::DependencyError(message) => {
+            format!("Failed to resolve dependency: {message}")
+        }
+    })
+}
+
+fn get_tank(matches: &ArgMatches, profile_name: &str, options: Options) -> Tank {
+    load_tank(&get_manifest(matches), profile_name, options).unwrap_or_else(|message| {
+        println!("{message}");
         std::process::exit(1)
     })
 }
 
+/// Keeps the tank loaded and repeats the command after every change. Only the changed
+/// files are refreshed, the tank is loaded again when the configuration changes.
+/// Files are watched before the command runs, so edits made during a build start the next one.
+fn command_watch(matches: &ArgMatches, profile_name: &str) {
+    let command = matches.get_one::<String>("command").map(String::as_str).unwrap_or("build");
+    let manifest = get_manifest(matches);
+    let get_watched = |tank: &Result<Tank, String>| match tank {
+        Ok(tank) => tank.get_watched_files(),
+        Err(_) => vec![manifest.clone()],
+    };
+    let exit = |message: String| -> ! {
+        println!("{message}");
+        std::process::exit(1)
+    };
+    let mut tank = load_tank(&manifest, profile_name, get_options(matches));
+    let mut watched = get_watched(&tank);
+    let mut watcher = Watcher::new(&watched).unwrap_or_else(|message| exit(message));
+    let mut programs: Vec<std::process::Child> = vec![];
+    loop {
+        for program in &mut programs {
+            let _ = program.kill();
+            let _ = program.wait();
+        }
+        programs.clear();
+        match &tank {
+            Ok(tank) => {
+                let result = match command {
+                    "check" => tank.check(),
+                    _ => tank.build(),
+                };
+                if result && command == "run" {
+                    programs = tank.spawn();
+                }
+            }
+            Err(message) => println!("{message}"),
+        }
+        println!("{:>RESULT_BORDER_WIDTH$} for changes of {} files", "Watching".cyan(), watched.len());
+        let changed = watcher.wait();
+        for file in &changed {
+            println!("{:>RESULT_BORDER_WIDTH$} '{}'", "Changed".yellow(), file);
+        }
+        let is_refreshed = match &tank {
+            Ok(tank) => tank.refresh(&changed),
+            Err(_) => false,
+        };
+        if !is_refreshed {
+            tank = load_tank(&manifest, profile_name, get_options(matches));
+        }
+        watched = get_watched(&tank);
+        watcher.watch(&watched).unwrap_or_else(|message| exit(message));
+    }
+}
+
+/// Both graphs are printed unless one of them is asked for
+fn command_graph(matches: &ArgMatches, tank: &Tank) -> bool {
+    let is_json = matches.get_one::<String>("format").map(String::as_str) == Some("json");
+    if let Some(header) = matches.get_one::<String>("rdeps") {
+        let units = match tank.get_rebuilt_units(header) {
+            Some(units) => units,
+            None => {
+                println!("'{header}' isn't used by any section");
+                return false;
+            }
+        };
+        if is_json {
+            let units: Vec<String> = units.iter().map(|unit| format!("\"{}\"", json::escape(unit))).collect();
+            println!("[{}]", units.join(", "));
+        } else {
+            units.iter().for_each(|unit| println!("{unit}"));
+        }
+        return true;
+    }
+    let sections = matches.get_flag("sections");
+    let includes = matches.get_flag("includes");
+    let mut graphs = vec![];
+    if sections || !includes {
+        graphs.push(tank.get_section_graph());
+    }
+    if includes || !sections {
+        graphs.push(tank.get_include_graph());
+    }
+    if is_json {
+        let graphs: Vec<String> = graphs
+            .iter()
+            .map(|graph| format!("\"{}\": {}", graph.get_name(), graph.to_json()))
+            .collect();
+        println!("{{{}}}", graphs.join(", "));
+    } else {
+        graphs.iter().for_each(|graph| print!("{}", graph.to_dot()));
+    }
+    true
+}
+
 fn command_new(matches: &ArgMatches) -> Result<(), &str>{
     let tank_name = matches.get_one::<String>("tank_name");
     if tank_name.is_none() {
@@ -82,15 +314,16 @@ fn command_new(matches: &ArgMatches) -> Result<(), &str>{
         println!("{err}");
         return Err("Failed to write basic configuration");
     }
-    return Ok(());
+    Ok(())
 }
 
 fn main() {
     let matches = cli().get_matches();
+    configure_colors(matches.subcommand().map(|(_, matches)| matches).unwrap_or(&matches));
     let mut result = false;
     match matches.subcommand() {
         Some(("new", matches)) => {
-            if let Err(err) = command_new(&matches) {
+            if let Err(err) = command_new(matches) {
                 println!("Error: {err}");
             }
         }
@@ -99,7 +332,7 @@ fn main() {
             if let Some(input) = matches.get_one::<String>("profile") {
                 profile = input.clone();
             }
-            let tank = get_tank(&profile);
+            let tank = get_tank(matches, &profile, get_options(matches));
             result = tank.check();
         }
         Some(("build", matches)) => {
@@ -107,7 +340,7 @@ fn main() {
             if let Some(input) = matches.get_one::<String>("profile") {
                 profile = input.clone();
             }
-            let tank = get_tank(&profile);
+            let tank = get_tank(matches, &profile, get_options(matches));
             result = tank.build();
         }
         Some(("run", matches)) => {
@@ -115,9 +348,33 @@ fn main() {
             if let Some(input) = matches.get_one::<String>("profile") {
                 profile = input.clone();
             }
-            let tank = get_tank(&profile);
+            let tank = get_tank(matches, &profile, get_options(matches));
             result = tank.run();
         }
+        Some(("watch", matches)) => {
+            let mut profile = String::from("debug");
+            if let Some(input) = matches.get_one::<String>("profile") {
+                profile = input.clone();
+            }
+            command_watch(matches, &profile);
+        }
+        Some(("graph", matches)) => {
+            let mut profile = String::from("debug");
+            if let Some(input) = matches.get_one::<String>("profile") {
+                profile = input.clone();
+            }
+            let tank = get_tank(matches, &profile, get_options(matches));
+            result = command_graph(matches, &tank);
+        }
+        Some(("toolchain", matches)) => {
+            let mut profile = String::from("debug");
+            if let Some(input) = matches.get_one::<String>("profile") {
+                profile = input.clone();
+            }
+            let tank = get_tank(matches, &profile, get_options(matches));
+            tank.get_toolchain().print();
+            result = true;
+        }
         None => {
             println!("Unexpected command")
         }
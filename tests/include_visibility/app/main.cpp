@@ -0,0 +1,8 @@
+#include <iostream>
+#include "core.hpp"
+
+int main()
+{
+    std::cout << "Answer is " << answer() << std::endl;
+    return 0;
+}
@@ -0,0 +1,6 @@
+#include "base.hpp"
+
+int base()
+{
+    return 21;
+}
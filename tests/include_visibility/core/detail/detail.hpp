@@ -0,0 +1,3 @@
+#pragma once
+
+constexpr int multiplier = 2;
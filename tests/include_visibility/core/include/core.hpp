@@ -0,0 +1,4 @@
+#pragma once
+#include "base.hpp"
+
+int answer();
@@ -0,0 +1,7 @@
+#include "core.hpp"
+#include "detail.hpp"
+
+int answer()
+{
+    return base() * multiplier;
+}
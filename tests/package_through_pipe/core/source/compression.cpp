@@ -0,0 +1,6 @@
+#include "compression.hpp"
+#include <zlib.h>
+
+const char *compression_version() {
+    return zlibVersion();
+}
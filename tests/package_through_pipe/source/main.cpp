@@ -0,0 +1,7 @@
+#include <iostream>
+#include "compression.hpp"
+
+int main() {
+    std::cout << "zlib " << compression_version() << std::endl;
+    return 0;
+}
@@ -0,0 +1,9 @@
+#include "pch.hpp"
+
+void print_words(const std::vector<std::string>& words);
+
+int main()
+{
+    print_words({"Hello", "from", "precompiled", "header"});
+    return 0;
+}
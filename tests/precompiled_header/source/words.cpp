@@ -0,0 +1,12 @@
+#include "pch.hpp"
+
+void print_words(const std::vector<std::string>& words)
+{
+    std::map<std::size_t, std::string> ordered;
+    for (std::size_t i = 0; i < words.size(); ++i) {
+        ordered[i] = words[i];
+    }
+    for (const auto& [index, word] : ordered) {
+        std::cout << word << (index + 1 == ordered.size() ? "\n" : " ");
+    }
+}
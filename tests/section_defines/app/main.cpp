@@ -0,0 +1,12 @@
+#include <iostream>
+#include "mylib.hpp"
+
+#ifdef MYLIB_BUILDING
+#error "Private defines of a piped section must not be visible"
+#endif
+
+int main()
+{
+    std::cout << APP_NAME << ": " << mylib_kind() << std::endl;
+    return 0;
+}
@@ -0,0 +1,7 @@
+#pragma once
+
+#ifndef MYLIB_STATIC
+#error "Public defines of a piped section must be visible"
+#endif
+
+const char* mylib_kind();
@@ -0,0 +1,10 @@
+#include "mylib.hpp"
+
+#ifndef MYLIB_BUILDING
+#error "Private defines must be visible inside of the section"
+#endif
+
+const char* mylib_kind()
+{
+    return "static";
+}
@@ -0,0 +1,9 @@
+#include <iostream>
+
+const char* get_platform();
+
+int main()
+{
+    std::cout << "Hello from " << get_platform() << std::endl;
+    return 0;
+}
@@ -0,0 +1,4 @@
+const char* get_platform()
+{
+    return "posix";
+}
@@ -0,0 +1,6 @@
+#include <windows.h>
+
+const char* get_platform()
+{
+    return "win32";
+}
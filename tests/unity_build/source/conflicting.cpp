@@ -0,0 +1,14 @@
+#include "functions.hpp"
+
+// Would clash with 'value' from first.cpp if both were in one unity file
+namespace {
+int value()
+{
+    return 3;
+}
+}
+
+int conflicting()
+{
+    return value();
+}
@@ -0,0 +1,13 @@
+#include "functions.hpp"
+
+namespace {
+int value()
+{
+    return 1;
+}
+}
+
+int first()
+{
+    return value();
+}
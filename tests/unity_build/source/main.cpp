@@ -0,0 +1,8 @@
+#include <iostream>
+#include "functions.hpp"
+
+int main()
+{
+    std::cout << first() + second() + conflicting() << std::endl;
+    return 0;
+}
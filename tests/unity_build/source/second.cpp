@@ -0,0 +1,6 @@
+#include "functions.hpp"
+
+int second()
+{
+    return 2;
+}
@@ -0,0 +1,7 @@
+void greet();
+
+int main()
+{
+    greet();
+    return 0;
+}
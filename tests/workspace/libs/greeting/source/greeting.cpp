@@ -0,0 +1,6 @@
+#include <iostream>
+
+void greet()
+{
+    std::cout << "Hello from workspace" << std::endl;
+}